@@ -0,0 +1,22 @@
+//! Tells whether FreeType is linked statically, as functions of newer FreeType
+//! are looked up at runtime only in a shared library
+
+use std::env;
+
+fn main() {
+    println!("cargo::rerun-if-changed=build.rs");
+    println!("cargo::rustc-check-cfg=cfg(freetype_static)");
+
+    // freetype-sys builds its bundled FreeType unless this finds the system one
+    let target = env::var("TARGET").unwrap();
+    let system = !target.contains("android")
+        && !target.contains("ohos")
+        && pkg_config::Config::new()
+            .atleast_version("24.3.18")
+            .cargo_metadata(false)
+            .probe("freetype2")
+            .is_ok();
+    if !system {
+        println!("cargo::rustc-cfg=freetype_static");
+    }
+}
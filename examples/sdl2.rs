@@ -1,14 +1,48 @@
-use clap::Parser;
-use rust_freetype_harfbuzz_example::{bitmap::StringBitmap, font::Font, string_bitmap_to_texture};
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use rust_freetype_harfbuzz_example::{
+    bitmap::StringBitmap,
+    database::{FamilyName, FontDatabase, FontQuery, FontStyle},
+    fallback::FontStack,
+    font::Font,
+    render::{self, AlphaMode, Blending, Hinting, RenderMode, TextStyle},
+    shaping::{Direction, Feature, ShapingOptions},
+    string_bitmap_to_texture,
+};
 use sdl2::{event::Event, keyboard::Keycode, pixels::Color, rect::Rect};
 
 /// Rendering example
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Font file name
+    /// Font file name, can be repeated to add fallback fonts in order of preference
+    ///
+    /// Fonts are looked up by `--family` and the other font properties if not given.
+    #[arg(long)]
+    font: Vec<String>,
+
+    /// Font family name or generic family (e.g. serif, monospace), can be repeated
+    #[arg(long)]
+    family: Vec<FamilyName>,
+
+    /// Font weight from 1 to 1000
+    #[arg(long, default_value_t = 400.0)]
+    weight: f32,
+
+    /// Font width in percent of normal width
+    #[arg(long, default_value_t = 100.0)]
+    stretch: f32,
+
+    /// Font style (normal, italic or oblique)
+    #[arg(long, default_value = "normal")]
+    font_style: FontStyle,
+
+    /// Directory to look up fonts in instead of system font directories, can be repeated
+    #[arg(long)]
+    font_dir: Vec<String>,
+
+    /// File to keep font database in, which makes later lookups faster
     #[arg(long)]
-    font: String,
+    font_cache: Option<String>,
 
     /// Text
     #[arg(long)]
@@ -39,20 +73,194 @@ struct Args {
     /// Window height
     #[arg(long, default_value_t = 800)]
     window_height: u32,
+
+    /// ISO 15924 script tag (e.g. Latn, Arab, Hani), guessed from text if omitted
+    #[arg(long)]
+    script: Option<String>,
+
+    /// BCP-47 language tag (e.g. ja, zh-Hans), guessed from text if omitted
+    #[arg(long)]
+    language: Option<String>,
+
+    /// Text direction (ltr, rtl or ttb), itemized by bidi algorithm if omitted
+    #[arg(long)]
+    direction: Option<Direction>,
+
+    /// Paragraph base direction (ltr or rtl), detected from text if omitted
+    #[arg(long)]
+    base_direction: Option<Direction>,
+
+    /// OpenType feature (e.g. -liga, +ss01, tnum, aalt[3:5]=2), can be repeated
+    #[arg(long)]
+    feature: Vec<Feature>,
+
+    /// Render mode (normal, mono, lcd, lcd-v or sdf)
+    #[arg(long, default_value = "lcd")]
+    render_mode: RenderMode,
+
+    /// Text color (#rrggbb or #rrggbbaa)
+    #[arg(long, default_value = "#ffffff")]
+    foreground: render::Color,
+
+    /// Background color (#rrggbb, #rrggbbaa or transparent)
+    #[arg(long, default_value = "transparent")]
+    background: render::Color,
+
+    /// Blending (srgb, linear or gamma:<gamma>,<contrast>)
+    #[arg(long, default_value = "srgb")]
+    blending: Blending,
+
+    /// Number of fractional positions a glyph can be rendered at within a pixel
+    #[arg(long, default_value_t = 4)]
+    subpixel_bins: u8,
+
+    /// Hinting (none, light, normal, mono or auto)
+    #[arg(long, default_value = "normal")]
+    hinting: Hinting,
+
+    /// Prefer embedded bitmaps to outlines
+    #[arg(long)]
+    embedded_bitmaps: bool,
+
+    /// Index of palette which color glyphs are painted with
+    #[arg(long, default_value_t = 0)]
+    palette: u16,
+
+    /// Variation axis coordinate of variable fonts (e.g. wght=650, opsz=12), can be repeated
+    #[arg(long, value_parser = parse_variation)]
+    variation: Vec<(String, f32)>,
+
+    /// Keep opsz axis at its default instead of following the font size
+    #[arg(long)]
+    no_optical_sizing: bool,
+}
+
+/// Parses `<tag>=<value>` of `--variation`
+fn parse_variation(s: &str) -> Result<(String, f32), String> {
+    let (tag, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected <tag>=<value>, got {:?}", s))?;
+    let value = value.trim().parse().map_err(|err| format!("{}", err))?;
+
+    Ok((tag.trim().to_string(), value))
+}
+
+/// Looks up one font for each family of `--family`, in order of preference
+fn fonts_from_database(args: &Args) -> Vec<Font> {
+    let cache = args.font_cache.as_deref();
+    let mut database = cache
+        .and_then(|cache| FontDatabase::load(cache).ok())
+        .unwrap_or_default();
+    if args.font_dir.is_empty() {
+        database
+            .scan_system_dirs()
+            .expect("Failed to scan font directories");
+    }
+    for dir in &args.font_dir {
+        database
+            .scan_dir(dir)
+            .expect("Failed to scan font directory");
+    }
+    if let Some(cache) = cache {
+        database.save(cache).expect("Failed to save font database");
+    }
+
+    let mut families: Vec<Vec<FamilyName>> = args
+        .family
+        .iter()
+        .map(|family| vec![family.clone()])
+        .collect();
+    if families.is_empty() {
+        families.push(Vec::new());
+    }
+    let mut faces = Vec::new();
+    for families in families {
+        let query = FontQuery {
+            families,
+            weight: args.weight,
+            stretch: args.stretch,
+            style: args.font_style,
+        };
+        let Some(face) = database.query(&query) else {
+            Args::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    "no font found in font directories",
+                )
+                .exit();
+        };
+        // Families missing from the database fall back to the same face
+        if !faces.iter().any(|(other, _)| *other == face) {
+            faces.push((face, query));
+        }
+    }
+
+    faces
+        .iter()
+        .map(|(face, query)| {
+            database
+                .load_face(face, query)
+                .expect("Failed to load font")
+        })
+        .collect()
 }
 
 pub fn render(args: &Args) -> StringBitmap {
     let text = args.text.as_str();
-    let mut face = Font::from_file(args.font.as_str(), 0);
+    let fonts = if args.font.is_empty() {
+        fonts_from_database(args)
+    } else {
+        args.font
+            .iter()
+            .map(|font| Font::from_file(font, 0).expect("Failed to load font"))
+            .collect()
+    };
+    let mut face = FontStack::new(fonts).expect("Failed to create font stack");
     face.set_dpi(args.hdpi, args.vdpi);
-    face.set_font_size(args.font_size);
+    face.set_font_size(args.font_size)
+        .expect("Failed to set font size");
     face.set_letter_spacing(args.letter_spacing);
+    face.set_render_mode(args.render_mode);
+    face.set_style(TextStyle {
+        foreground: args.foreground,
+        background: args.background,
+        alpha_mode: AlphaMode::Straight,
+        blending: args.blending,
+    });
+    face.set_subpixel_bins(args.subpixel_bins);
+    face.set_hinting(args.hinting);
+    face.set_embedded_bitmaps(args.embedded_bitmaps);
+    face.set_palette(args.palette);
+    let variations: Vec<(&str, f32)> = args
+        .variation
+        .iter()
+        .map(|(tag, value)| (tag.as_str(), *value))
+        .collect();
+    // Fonts from the database already have weight and stretch set on their axes
+    if !variations.is_empty() {
+        face.set_variations(&variations)
+            .expect("Failed to set variations");
+    }
+    face.set_optical_sizing(!args.no_optical_sizing);
+    let options = ShapingOptions {
+        script: args.script.clone(),
+        language: args.language.clone(),
+        direction: args.direction,
+        base_direction: args.base_direction,
+        features: args.feature.clone(),
+        ..Default::default()
+    };
 
-    face.render(text).unwrap()
+    face.render(text, &options).unwrap()
 }
 
 pub fn main() -> Result<(), String> {
     let args = Args::parse();
+    if args.direction == Some(Direction::BottomToTop) {
+        Args::command()
+            .error(ErrorKind::InvalidValue, "--direction btt is not supported")
+            .exit();
+    }
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
 
@@ -1,10 +1,17 @@
+use crate::{
+    error::{Error, Result},
+    render::{linear_to_srgb, srgb_to_linear, AlphaMode, TextStyle},
+};
+
 /// Measured size of string bitmap
 #[derive(Clone, Copy)]
 pub struct StringBitmapSize {
     pub width: u64,
     pub height: u64,
-    pub(crate) y_min: u64,
+    /// Distance from the top edge to the baseline, or to the top of vertical text
     pub(crate) y_max: u64,
+    /// Distance from the left edge to the origin of horizontal text, or to the center line of vertical text
+    pub(crate) x_min: u64,
 }
 
 // Rendered string bitmap
@@ -14,26 +21,106 @@ pub struct StringBitmap {
     pub b: Vec<u8>,
     pub a: Vec<u8>,
     pub size: StringBitmapSize,
+    /// Whether r, g and b are premultiplied by a
+    pub alpha_mode: AlphaMode,
 }
 
 impl StringBitmap {
-    pub(crate) fn new(size: StringBitmapSize) -> StringBitmap {
-        StringBitmap {
-            r: vec![0; (size.width * size.height).try_into().expect("Too big")],
-            g: vec![0; (size.width * size.height).try_into().expect("Too big")],
-            b: vec![0; (size.width * size.height).try_into().expect("Too big")],
-            a: vec![0; (size.width * size.height).try_into().expect("Too big")],
-            size: size.clone(),
-        }
+    /// Creates bitmap filled with background of `style`
+    pub(crate) fn new(size: StringBitmapSize, style: &TextStyle) -> Result<StringBitmap> {
+        let len: usize = size
+            .width
+            .checked_mul(size.height)
+            .ok_or(Error::SizeOverflow)?
+            .try_into()
+            .map_err(|_| Error::SizeOverflow)?;
+
+        let background = style.background;
+        let premultiply = |channel: u8| match style.alpha_mode {
+            AlphaMode::Straight => channel,
+            AlphaMode::Premultiplied => (channel as u32 * background.a as u32 / 255) as u8,
+        };
+
+        Ok(StringBitmap {
+            r: vec![premultiply(background.r); len],
+            g: vec![premultiply(background.g); len],
+            b: vec![premultiply(background.b); len],
+            a: vec![background.a; len],
+            size,
+            alpha_mode: style.alpha_mode,
+        })
     }
 
-    pub(crate) fn set_rgba(&mut self, x: i64, y: i64, rgba: (u8, u8, u8, u8)) {
+    /// Composites color over pixel, pixels outside of bitmap are clipped
+    ///
+    /// `color` is premultiplied by `alpha`, and every channel has its own alpha
+    /// so that subpixel coverage can be blended per channel.
+    /// If `linear` is true, `color` is in linear light and blending is done in linear light.
+    /// All values are in range of `0.0..=1.0`.
+    pub(crate) fn composite(
+        &mut self,
+        x: i64,
+        y: i64,
+        color: [f32; 3],
+        alpha: [f32; 3],
+        linear: bool,
+    ) {
+        if x < 0 || y < 0 || x >= self.size.width as i64 || y >= self.size.height as i64 {
+            return;
+        }
         let pos = self.get_pos(x, y);
 
-        self.r[pos] = rgba.0;
-        self.g[pos] = rgba.1;
-        self.b[pos] = rgba.2;
-        self.a[pos] = rgba.3;
+        let dst_alpha = self.a[pos] as f32 / 255.0;
+        let to_premultiplied = |channel: u8| {
+            let straight = match self.alpha_mode {
+                AlphaMode::Straight => channel,
+                AlphaMode::Premultiplied if self.a[pos] == 0 => 0,
+                AlphaMode::Premultiplied => {
+                    std::cmp::min(channel as u32 * 255 / self.a[pos] as u32, 255) as u8
+                }
+            };
+            let straight = if linear {
+                srgb_to_linear(straight)
+            } else {
+                straight as f32 / 255.0
+            };
+
+            straight * dst_alpha
+        };
+        let dst = [
+            to_premultiplied(self.r[pos]),
+            to_premultiplied(self.g[pos]),
+            to_premultiplied(self.b[pos]),
+        ];
+
+        // Porter-Duff "over" for each channel, coverage of the pixel is the largest one
+        let out = [0, 1, 2].map(|i| color[i] + dst[i] * (1.0 - alpha[i]));
+        let src_alpha = alpha[0].max(alpha[1]).max(alpha[2]);
+        let out_alpha = src_alpha + dst_alpha * (1.0 - src_alpha);
+
+        let from_premultiplied = |channel: f32| {
+            let straight = if out_alpha > 0.0 {
+                channel / out_alpha
+            } else {
+                0.0
+            };
+            let straight = if linear {
+                linear_to_srgb(straight) as f32 / 255.0
+            } else {
+                straight.clamp(0.0, 1.0)
+            };
+            let channel = match self.alpha_mode {
+                AlphaMode::Straight => straight,
+                AlphaMode::Premultiplied => straight * out_alpha,
+            };
+
+            (channel * 255.0).round() as u8
+        };
+        let rgb = out.map(from_premultiplied);
+        self.r[pos] = rgb[0];
+        self.g[pos] = rgb[1];
+        self.b[pos] = rgb[2];
+        self.a[pos] = (out_alpha.clamp(0.0, 1.0) * 255.0).round() as u8;
     }
 
     pub(crate) fn get_pos(&self, x: i64, y: i64) -> usize {
@@ -42,7 +129,7 @@ impl StringBitmap {
 
     pub fn get_rgba(&self, x: i64, y: i64) -> (u8, u8, u8, u8) {
         let pos = self.get_pos(x, y);
-        return (self.r[pos], self.g[pos], self.b[pos], self.a[pos]);
+        (self.r[pos], self.g[pos], self.b[pos], self.a[pos])
     }
 }
 
@@ -0,0 +1,618 @@
+use std::{collections::HashMap, ops::Range, sync::OnceLock};
+
+use harfbuzz_sys::{HB_UNICODE_GENERAL_CATEGORY_CONTROL, HB_UNICODE_GENERAL_CATEGORY_UNASSIGNED};
+
+use crate::harfbuzz::unicode;
+
+/// Script tag of characters which belong to no script, such as private use characters
+const SCRIPT_UNKNOWN: [u8; 4] = *b"Zzzz";
+
+/// Set of characters a font has glyphs for, as sorted ranges of code points
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Coverage {
+    ranges: Vec<Range<u32>>,
+}
+
+/// How much of one script a font covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptCoverage {
+    /// ISO 15924 script tag such as `Latn`, `Zyyy` (common) or `Zinh` (inherited)
+    pub script: [u8; 4],
+    /// Number of characters of the script the font has glyphs for
+    pub covered: u32,
+    /// Number of characters of the script
+    pub total: u32,
+}
+
+/// How much of one Unicode block a font covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockCoverage {
+    /// Block name such as `Basic Latin`
+    pub name: &'static str,
+    pub range: Range<u32>,
+    /// Number of characters of the block the font has glyphs for
+    pub covered: u32,
+    /// Number of characters in the block
+    pub total: u32,
+}
+
+impl ScriptCoverage {
+    /// Covered characters in percent of all characters
+    pub fn percent(&self) -> f32 {
+        percent(self.covered, self.total)
+    }
+}
+
+impl BlockCoverage {
+    /// Covered characters in percent of all characters
+    pub fn percent(&self) -> f32 {
+        percent(self.covered, self.total)
+    }
+}
+
+impl Coverage {
+    /// Creates coverage from sorted ranges which neither overlap nor touch each other
+    pub(crate) fn from_ranges(ranges: Vec<Range<u32>>) -> Coverage {
+        Coverage { ranges }
+    }
+
+    /// Sorted ranges of code points, merged wherever they are consecutive
+    pub fn ranges(&self) -> &[Range<u32>] {
+        &self.ranges
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        let code = c as u32;
+        let position = self.ranges.partition_point(|range| range.end <= code);
+
+        self.ranges
+            .get(position)
+            .is_some_and(|range| range.contains(&code))
+    }
+
+    /// Number of characters in the set
+    pub fn len(&self) -> usize {
+        self.ranges.iter().map(|range| range.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Every character in the set in order of code points
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.ranges
+            .iter()
+            .flat_map(|range| range.clone().filter_map(char::from_u32))
+    }
+
+    /// Coverage of every script the set has any character of, sorted by tag
+    ///
+    /// Characters of unknown script such as private use characters are not counted,
+    /// nor are control characters and code points unassigned in the Unicode version HarfBuzz knows.
+    pub fn scripts(&self) -> Vec<ScriptCoverage> {
+        let mut covered: HashMap<[u8; 4], u32> = HashMap::new();
+        for (run, script) in self
+            .ranges
+            .iter()
+            .flat_map(|range| runs_within(range.clone()))
+        {
+            *covered.entry(script).or_default() += run.len() as u32;
+        }
+        covered.remove(&SCRIPT_UNKNOWN);
+
+        let mut total: HashMap<[u8; 4], u32> = HashMap::new();
+        for (run, script) in script_runs() {
+            if covered.contains_key(script) {
+                *total.entry(*script).or_default() += run.len() as u32;
+            }
+        }
+
+        let mut scripts: Vec<ScriptCoverage> = covered
+            .into_iter()
+            .map(|(script, covered)| ScriptCoverage {
+                script,
+                covered,
+                total: total[&script],
+            })
+            .collect();
+        scripts.sort_by_key(|script| script.script);
+
+        scripts
+    }
+
+    /// Coverage of every Unicode block the set has any character of, in order of code points
+    ///
+    /// Blocks are those of Unicode 15.0.
+    /// Control characters and unassigned code points are not counted.
+    pub fn blocks(&self) -> Vec<BlockCoverage> {
+        BLOCKS
+            .iter()
+            .filter_map(|(range, name)| {
+                let covered = self
+                    .ranges
+                    .iter()
+                    .map(|covered| covered.start.max(range.start)..covered.end.min(range.end))
+                    .filter(|overlap| !overlap.is_empty())
+                    .flat_map(runs_within)
+                    .map(|(run, _)| run.len() as u32)
+                    .sum();
+                if covered == 0 {
+                    return None;
+                }
+
+                Some(BlockCoverage {
+                    name,
+                    range: range.clone(),
+                    covered,
+                    total: runs_within(range.clone())
+                        .map(|(run, _)| run.len() as u32)
+                        .sum(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Maximal runs of counted characters of one script, in order of code points
+///
+/// Every code point is looked up only once, when coverage of scripts or blocks is first asked.
+fn script_runs() -> &'static [(Range<u32>, [u8; 4])] {
+    static RUNS: OnceLock<Vec<(Range<u32>, [u8; 4])>> = OnceLock::new();
+    RUNS.get_or_init(|| {
+        let mut runs: Vec<(Range<u32>, [u8; 4])> = Vec::new();
+        for c in ('\0'..=char::MAX).filter(|c| is_counted(*c)) {
+            let (code, script) = (c as u32, unicode::script(c));
+            match runs.last_mut() {
+                Some((run, last)) if run.end == code && *last == script => run.end += 1,
+                _ => runs.push((code..code + 1, script)),
+            }
+        }
+
+        runs
+    })
+}
+
+/// Runs of `script_runs` overlapping `range`, cut to it
+fn runs_within(range: Range<u32>) -> impl Iterator<Item = (Range<u32>, [u8; 4])> {
+    let runs = script_runs();
+    let first = runs.partition_point(|(run, _)| run.end <= range.start);
+
+    runs[first..]
+        .iter()
+        .take_while(move |(run, _)| run.start < range.end)
+        .map(move |(run, script)| (run.start.max(range.start)..run.end.min(range.end), *script))
+}
+
+/// Whether `c` counts towards coverage percentages
+fn is_counted(c: char) -> bool {
+    let category = unicode::general_category(c);
+
+    category != HB_UNICODE_GENERAL_CATEGORY_UNASSIGNED
+        && category != HB_UNICODE_GENERAL_CATEGORY_CONTROL
+}
+
+fn percent(covered: u32, total: u32) -> f32 {
+    if total == 0 {
+        return 0.0;
+    }
+
+    covered as f32 * 100.0 / total as f32
+}
+
+/// Unicode blocks of Unicode 15.0
+const BLOCKS: &[(Range<u32>, &str)] = &[
+    (0x0000..0x0080, "Basic Latin"),
+    (0x0080..0x0100, "Latin-1 Supplement"),
+    (0x0100..0x0180, "Latin Extended-A"),
+    (0x0180..0x0250, "Latin Extended-B"),
+    (0x0250..0x02B0, "IPA Extensions"),
+    (0x02B0..0x0300, "Spacing Modifier Letters"),
+    (0x0300..0x0370, "Combining Diacritical Marks"),
+    (0x0370..0x0400, "Greek and Coptic"),
+    (0x0400..0x0500, "Cyrillic"),
+    (0x0500..0x0530, "Cyrillic Supplement"),
+    (0x0530..0x0590, "Armenian"),
+    (0x0590..0x0600, "Hebrew"),
+    (0x0600..0x0700, "Arabic"),
+    (0x0700..0x0750, "Syriac"),
+    (0x0750..0x0780, "Arabic Supplement"),
+    (0x0780..0x07C0, "Thaana"),
+    (0x07C0..0x0800, "NKo"),
+    (0x0800..0x0840, "Samaritan"),
+    (0x0840..0x0860, "Mandaic"),
+    (0x0860..0x0870, "Syriac Supplement"),
+    (0x0870..0x08A0, "Arabic Extended-B"),
+    (0x08A0..0x0900, "Arabic Extended-A"),
+    (0x0900..0x0980, "Devanagari"),
+    (0x0980..0x0A00, "Bengali"),
+    (0x0A00..0x0A80, "Gurmukhi"),
+    (0x0A80..0x0B00, "Gujarati"),
+    (0x0B00..0x0B80, "Oriya"),
+    (0x0B80..0x0C00, "Tamil"),
+    (0x0C00..0x0C80, "Telugu"),
+    (0x0C80..0x0D00, "Kannada"),
+    (0x0D00..0x0D80, "Malayalam"),
+    (0x0D80..0x0E00, "Sinhala"),
+    (0x0E00..0x0E80, "Thai"),
+    (0x0E80..0x0F00, "Lao"),
+    (0x0F00..0x1000, "Tibetan"),
+    (0x1000..0x10A0, "Myanmar"),
+    (0x10A0..0x1100, "Georgian"),
+    (0x1100..0x1200, "Hangul Jamo"),
+    (0x1200..0x1380, "Ethiopic"),
+    (0x1380..0x13A0, "Ethiopic Supplement"),
+    (0x13A0..0x1400, "Cherokee"),
+    (0x1400..0x1680, "Unified Canadian Aboriginal Syllabics"),
+    (0x1680..0x16A0, "Ogham"),
+    (0x16A0..0x1700, "Runic"),
+    (0x1700..0x1720, "Tagalog"),
+    (0x1720..0x1740, "Hanunoo"),
+    (0x1740..0x1760, "Buhid"),
+    (0x1760..0x1780, "Tagbanwa"),
+    (0x1780..0x1800, "Khmer"),
+    (0x1800..0x18B0, "Mongolian"),
+    (
+        0x18B0..0x1900,
+        "Unified Canadian Aboriginal Syllabics Extended",
+    ),
+    (0x1900..0x1950, "Limbu"),
+    (0x1950..0x1980, "Tai Le"),
+    (0x1980..0x19E0, "New Tai Lue"),
+    (0x19E0..0x1A00, "Khmer Symbols"),
+    (0x1A00..0x1A20, "Buginese"),
+    (0x1A20..0x1AB0, "Tai Tham"),
+    (0x1AB0..0x1B00, "Combining Diacritical Marks Extended"),
+    (0x1B00..0x1B80, "Balinese"),
+    (0x1B80..0x1BC0, "Sundanese"),
+    (0x1BC0..0x1C00, "Batak"),
+    (0x1C00..0x1C50, "Lepcha"),
+    (0x1C50..0x1C80, "Ol Chiki"),
+    (0x1C80..0x1C90, "Cyrillic Extended-C"),
+    (0x1C90..0x1CC0, "Georgian Extended"),
+    (0x1CC0..0x1CD0, "Sundanese Supplement"),
+    (0x1CD0..0x1D00, "Vedic Extensions"),
+    (0x1D00..0x1D80, "Phonetic Extensions"),
+    (0x1D80..0x1DC0, "Phonetic Extensions Supplement"),
+    (0x1DC0..0x1E00, "Combining Diacritical Marks Supplement"),
+    (0x1E00..0x1F00, "Latin Extended Additional"),
+    (0x1F00..0x2000, "Greek Extended"),
+    (0x2000..0x2070, "General Punctuation"),
+    (0x2070..0x20A0, "Superscripts and Subscripts"),
+    (0x20A0..0x20D0, "Currency Symbols"),
+    (0x20D0..0x2100, "Combining Diacritical Marks for Symbols"),
+    (0x2100..0x2150, "Letterlike Symbols"),
+    (0x2150..0x2190, "Number Forms"),
+    (0x2190..0x2200, "Arrows"),
+    (0x2200..0x2300, "Mathematical Operators"),
+    (0x2300..0x2400, "Miscellaneous Technical"),
+    (0x2400..0x2440, "Control Pictures"),
+    (0x2440..0x2460, "Optical Character Recognition"),
+    (0x2460..0x2500, "Enclosed Alphanumerics"),
+    (0x2500..0x2580, "Box Drawing"),
+    (0x2580..0x25A0, "Block Elements"),
+    (0x25A0..0x2600, "Geometric Shapes"),
+    (0x2600..0x2700, "Miscellaneous Symbols"),
+    (0x2700..0x27C0, "Dingbats"),
+    (0x27C0..0x27F0, "Miscellaneous Mathematical Symbols-A"),
+    (0x27F0..0x2800, "Supplemental Arrows-A"),
+    (0x2800..0x2900, "Braille Patterns"),
+    (0x2900..0x2980, "Supplemental Arrows-B"),
+    (0x2980..0x2A00, "Miscellaneous Mathematical Symbols-B"),
+    (0x2A00..0x2B00, "Supplemental Mathematical Operators"),
+    (0x2B00..0x2C00, "Miscellaneous Symbols and Arrows"),
+    (0x2C00..0x2C60, "Glagolitic"),
+    (0x2C60..0x2C80, "Latin Extended-C"),
+    (0x2C80..0x2D00, "Coptic"),
+    (0x2D00..0x2D30, "Georgian Supplement"),
+    (0x2D30..0x2D80, "Tifinagh"),
+    (0x2D80..0x2DE0, "Ethiopic Extended"),
+    (0x2DE0..0x2E00, "Cyrillic Extended-A"),
+    (0x2E00..0x2E80, "Supplemental Punctuation"),
+    (0x2E80..0x2F00, "CJK Radicals Supplement"),
+    (0x2F00..0x2FE0, "Kangxi Radicals"),
+    (0x2FF0..0x3000, "Ideographic Description Characters"),
+    (0x3000..0x3040, "CJK Symbols and Punctuation"),
+    (0x3040..0x30A0, "Hiragana"),
+    (0x30A0..0x3100, "Katakana"),
+    (0x3100..0x3130, "Bopomofo"),
+    (0x3130..0x3190, "Hangul Compatibility Jamo"),
+    (0x3190..0x31A0, "Kanbun"),
+    (0x31A0..0x31C0, "Bopomofo Extended"),
+    (0x31C0..0x31F0, "CJK Strokes"),
+    (0x31F0..0x3200, "Katakana Phonetic Extensions"),
+    (0x3200..0x3300, "Enclosed CJK Letters and Months"),
+    (0x3300..0x3400, "CJK Compatibility"),
+    (0x3400..0x4DC0, "CJK Unified Ideographs Extension A"),
+    (0x4DC0..0x4E00, "Yijing Hexagram Symbols"),
+    (0x4E00..0xA000, "CJK Unified Ideographs"),
+    (0xA000..0xA490, "Yi Syllables"),
+    (0xA490..0xA4D0, "Yi Radicals"),
+    (0xA4D0..0xA500, "Lisu"),
+    (0xA500..0xA640, "Vai"),
+    (0xA640..0xA6A0, "Cyrillic Extended-B"),
+    (0xA6A0..0xA700, "Bamum"),
+    (0xA700..0xA720, "Modifier Tone Letters"),
+    (0xA720..0xA800, "Latin Extended-D"),
+    (0xA800..0xA830, "Syloti Nagri"),
+    (0xA830..0xA840, "Common Indic Number Forms"),
+    (0xA840..0xA880, "Phags-pa"),
+    (0xA880..0xA8E0, "Saurashtra"),
+    (0xA8E0..0xA900, "Devanagari Extended"),
+    (0xA900..0xA930, "Kayah Li"),
+    (0xA930..0xA960, "Rejang"),
+    (0xA960..0xA980, "Hangul Jamo Extended-A"),
+    (0xA980..0xA9E0, "Javanese"),
+    (0xA9E0..0xAA00, "Myanmar Extended-B"),
+    (0xAA00..0xAA60, "Cham"),
+    (0xAA60..0xAA80, "Myanmar Extended-A"),
+    (0xAA80..0xAAE0, "Tai Viet"),
+    (0xAAE0..0xAB00, "Meetei Mayek Extensions"),
+    (0xAB00..0xAB30, "Ethiopic Extended-A"),
+    (0xAB30..0xAB70, "Latin Extended-E"),
+    (0xAB70..0xABC0, "Cherokee Supplement"),
+    (0xABC0..0xAC00, "Meetei Mayek"),
+    (0xAC00..0xD7B0, "Hangul Syllables"),
+    (0xD7B0..0xD800, "Hangul Jamo Extended-B"),
+    (0xD800..0xDB80, "High Surrogates"),
+    (0xDB80..0xDC00, "High Private Use Surrogates"),
+    (0xDC00..0xE000, "Low Surrogates"),
+    (0xE000..0xF900, "Private Use Area"),
+    (0xF900..0xFB00, "CJK Compatibility Ideographs"),
+    (0xFB00..0xFB50, "Alphabetic Presentation Forms"),
+    (0xFB50..0xFE00, "Arabic Presentation Forms-A"),
+    (0xFE00..0xFE10, "Variation Selectors"),
+    (0xFE10..0xFE20, "Vertical Forms"),
+    (0xFE20..0xFE30, "Combining Half Marks"),
+    (0xFE30..0xFE50, "CJK Compatibility Forms"),
+    (0xFE50..0xFE70, "Small Form Variants"),
+    (0xFE70..0xFF00, "Arabic Presentation Forms-B"),
+    (0xFF00..0xFFF0, "Halfwidth and Fullwidth Forms"),
+    (0xFFF0..0x10000, "Specials"),
+    (0x10000..0x10080, "Linear B Syllabary"),
+    (0x10080..0x10100, "Linear B Ideograms"),
+    (0x10100..0x10140, "Aegean Numbers"),
+    (0x10140..0x10190, "Ancient Greek Numbers"),
+    (0x10190..0x101D0, "Ancient Symbols"),
+    (0x101D0..0x10200, "Phaistos Disc"),
+    (0x10280..0x102A0, "Lycian"),
+    (0x102A0..0x102E0, "Carian"),
+    (0x102E0..0x10300, "Coptic Epact Numbers"),
+    (0x10300..0x10330, "Old Italic"),
+    (0x10330..0x10350, "Gothic"),
+    (0x10350..0x10380, "Old Permic"),
+    (0x10380..0x103A0, "Ugaritic"),
+    (0x103A0..0x103E0, "Old Persian"),
+    (0x10400..0x10450, "Deseret"),
+    (0x10450..0x10480, "Shavian"),
+    (0x10480..0x104B0, "Osmanya"),
+    (0x104B0..0x10500, "Osage"),
+    (0x10500..0x10530, "Elbasan"),
+    (0x10530..0x10570, "Caucasian Albanian"),
+    (0x10570..0x105C0, "Vithkuqi"),
+    (0x10600..0x10780, "Linear A"),
+    (0x10780..0x107C0, "Latin Extended-F"),
+    (0x10800..0x10840, "Cypriot Syllabary"),
+    (0x10840..0x10860, "Imperial Aramaic"),
+    (0x10860..0x10880, "Palmyrene"),
+    (0x10880..0x108B0, "Nabataean"),
+    (0x108E0..0x10900, "Hatran"),
+    (0x10900..0x10920, "Phoenician"),
+    (0x10920..0x10940, "Lydian"),
+    (0x10980..0x109A0, "Meroitic Hieroglyphs"),
+    (0x109A0..0x10A00, "Meroitic Cursive"),
+    (0x10A00..0x10A60, "Kharoshthi"),
+    (0x10A60..0x10A80, "Old South Arabian"),
+    (0x10A80..0x10AA0, "Old North Arabian"),
+    (0x10AC0..0x10B00, "Manichaean"),
+    (0x10B00..0x10B40, "Avestan"),
+    (0x10B40..0x10B60, "Inscriptional Parthian"),
+    (0x10B60..0x10B80, "Inscriptional Pahlavi"),
+    (0x10B80..0x10BB0, "Psalter Pahlavi"),
+    (0x10C00..0x10C50, "Old Turkic"),
+    (0x10C80..0x10D00, "Old Hungarian"),
+    (0x10D00..0x10D40, "Hanifi Rohingya"),
+    (0x10E60..0x10E80, "Rumi Numeral Symbols"),
+    (0x10E80..0x10EC0, "Yezidi"),
+    (0x10EC0..0x10F00, "Arabic Extended-C"),
+    (0x10F00..0x10F30, "Old Sogdian"),
+    (0x10F30..0x10F70, "Sogdian"),
+    (0x10F70..0x10FB0, "Old Uyghur"),
+    (0x10FB0..0x10FE0, "Chorasmian"),
+    (0x10FE0..0x11000, "Elymaic"),
+    (0x11000..0x11080, "Brahmi"),
+    (0x11080..0x110D0, "Kaithi"),
+    (0x110D0..0x11100, "Sora Sompeng"),
+    (0x11100..0x11150, "Chakma"),
+    (0x11150..0x11180, "Mahajani"),
+    (0x11180..0x111E0, "Sharada"),
+    (0x111E0..0x11200, "Sinhala Archaic Numbers"),
+    (0x11200..0x11250, "Khojki"),
+    (0x11280..0x112B0, "Multani"),
+    (0x112B0..0x11300, "Khudawadi"),
+    (0x11300..0x11380, "Grantha"),
+    (0x11400..0x11480, "Newa"),
+    (0x11480..0x114E0, "Tirhuta"),
+    (0x11580..0x11600, "Siddham"),
+    (0x11600..0x11660, "Modi"),
+    (0x11660..0x11680, "Mongolian Supplement"),
+    (0x11680..0x116D0, "Takri"),
+    (0x11700..0x11750, "Ahom"),
+    (0x11800..0x11850, "Dogra"),
+    (0x118A0..0x11900, "Warang Citi"),
+    (0x11900..0x11960, "Dives Akuru"),
+    (0x119A0..0x11A00, "Nandinagari"),
+    (0x11A00..0x11A50, "Zanabazar Square"),
+    (0x11A50..0x11AB0, "Soyombo"),
+    (
+        0x11AB0..0x11AC0,
+        "Unified Canadian Aboriginal Syllabics Extended-A",
+    ),
+    (0x11AC0..0x11B00, "Pau Cin Hau"),
+    (0x11B00..0x11B60, "Devanagari Extended-A"),
+    (0x11C00..0x11C70, "Bhaiksuki"),
+    (0x11C70..0x11CC0, "Marchen"),
+    (0x11D00..0x11D60, "Masaram Gondi"),
+    (0x11D60..0x11DB0, "Gunjala Gondi"),
+    (0x11EE0..0x11F00, "Makasar"),
+    (0x11F00..0x11F60, "Kawi"),
+    (0x11FB0..0x11FC0, "Lisu Supplement"),
+    (0x11FC0..0x12000, "Tamil Supplement"),
+    (0x12000..0x12400, "Cuneiform"),
+    (0x12400..0x12480, "Cuneiform Numbers and Punctuation"),
+    (0x12480..0x12550, "Early Dynastic Cuneiform"),
+    (0x12F90..0x13000, "Cypro-Minoan"),
+    (0x13000..0x13430, "Egyptian Hieroglyphs"),
+    (0x13430..0x13440, "Egyptian Hieroglyph Format Controls"),
+    (0x14400..0x14680, "Anatolian Hieroglyphs"),
+    (0x16800..0x16A40, "Bamum Supplement"),
+    (0x16A40..0x16A70, "Mro"),
+    (0x16A70..0x16AD0, "Tangsa"),
+    (0x16AD0..0x16B00, "Bassa Vah"),
+    (0x16B00..0x16B90, "Pahawh Hmong"),
+    (0x16E40..0x16EA0, "Medefaidrin"),
+    (0x16F00..0x16FA0, "Miao"),
+    (0x16FE0..0x17000, "Ideographic Symbols and Punctuation"),
+    (0x17000..0x18800, "Tangut"),
+    (0x18800..0x18B00, "Tangut Components"),
+    (0x18B00..0x18D00, "Khitan Small Script"),
+    (0x18D00..0x18D80, "Tangut Supplement"),
+    (0x1AFF0..0x1B000, "Kana Extended-B"),
+    (0x1B000..0x1B100, "Kana Supplement"),
+    (0x1B100..0x1B130, "Kana Extended-A"),
+    (0x1B130..0x1B170, "Small Kana Extension"),
+    (0x1B170..0x1B300, "Nushu"),
+    (0x1BC00..0x1BCA0, "Duployan"),
+    (0x1BCA0..0x1BCB0, "Shorthand Format Controls"),
+    (0x1CF00..0x1CFD0, "Znamenny Musical Notation"),
+    (0x1D000..0x1D100, "Byzantine Musical Symbols"),
+    (0x1D100..0x1D200, "Musical Symbols"),
+    (0x1D200..0x1D250, "Ancient Greek Musical Notation"),
+    (0x1D2C0..0x1D2E0, "Kaktovik Numerals"),
+    (0x1D2E0..0x1D300, "Mayan Numerals"),
+    (0x1D300..0x1D360, "Tai Xuan Jing Symbols"),
+    (0x1D360..0x1D380, "Counting Rod Numerals"),
+    (0x1D400..0x1D800, "Mathematical Alphanumeric Symbols"),
+    (0x1D800..0x1DAB0, "Sutton SignWriting"),
+    (0x1DF00..0x1E000, "Latin Extended-G"),
+    (0x1E000..0x1E030, "Glagolitic Supplement"),
+    (0x1E030..0x1E090, "Cyrillic Extended-D"),
+    (0x1E100..0x1E150, "Nyiakeng Puachue Hmong"),
+    (0x1E290..0x1E2C0, "Toto"),
+    (0x1E2C0..0x1E300, "Wancho"),
+    (0x1E4D0..0x1E500, "Nag Mundari"),
+    (0x1E7E0..0x1E800, "Ethiopic Extended-B"),
+    (0x1E800..0x1E8E0, "Mende Kikakui"),
+    (0x1E900..0x1E960, "Adlam"),
+    (0x1EC70..0x1ECC0, "Indic Siyaq Numbers"),
+    (0x1ED00..0x1ED50, "Ottoman Siyaq Numbers"),
+    (0x1EE00..0x1EF00, "Arabic Mathematical Alphabetic Symbols"),
+    (0x1F000..0x1F030, "Mahjong Tiles"),
+    (0x1F030..0x1F0A0, "Domino Tiles"),
+    (0x1F0A0..0x1F100, "Playing Cards"),
+    (0x1F100..0x1F200, "Enclosed Alphanumeric Supplement"),
+    (0x1F200..0x1F300, "Enclosed Ideographic Supplement"),
+    (0x1F300..0x1F600, "Miscellaneous Symbols and Pictographs"),
+    (0x1F600..0x1F650, "Emoticons"),
+    (0x1F650..0x1F680, "Ornamental Dingbats"),
+    (0x1F680..0x1F700, "Transport and Map Symbols"),
+    (0x1F700..0x1F780, "Alchemical Symbols"),
+    (0x1F780..0x1F800, "Geometric Shapes Extended"),
+    (0x1F800..0x1F900, "Supplemental Arrows-C"),
+    (0x1F900..0x1FA00, "Supplemental Symbols and Pictographs"),
+    (0x1FA00..0x1FA70, "Chess Symbols"),
+    (0x1FA70..0x1FB00, "Symbols and Pictographs Extended-A"),
+    (0x1FB00..0x1FC00, "Symbols for Legacy Computing"),
+    (0x20000..0x2A6E0, "CJK Unified Ideographs Extension B"),
+    (0x2A700..0x2B740, "CJK Unified Ideographs Extension C"),
+    (0x2B740..0x2B820, "CJK Unified Ideographs Extension D"),
+    (0x2B820..0x2CEB0, "CJK Unified Ideographs Extension E"),
+    (0x2CEB0..0x2EBF0, "CJK Unified Ideographs Extension F"),
+    (0x2F800..0x2FA20, "CJK Compatibility Ideographs Supplement"),
+    (0x30000..0x31350, "CJK Unified Ideographs Extension G"),
+    (0x31350..0x323B0, "CJK Unified Ideographs Extension H"),
+    (0xE0000..0xE0080, "Tags"),
+    (0xE0100..0xE01F0, "Variation Selectors Supplement"),
+    (0xF0000..0x100000, "Supplementary Private Use Area-A"),
+    (0x100000..0x110000, "Supplementary Private Use Area-B"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coverage(ranges: &[Range<u32>]) -> Coverage {
+        Coverage::from_ranges(ranges.to_vec())
+    }
+
+    #[test]
+    fn looks_up_characters_in_ranges() {
+        let coverage = coverage(&[0x41..0x5b, 0x61..0x7b, 0x3b1..0x3c2]);
+
+        assert!(coverage.contains('A') && coverage.contains('Z') && coverage.contains('z'));
+        assert!(!coverage.contains('@') && !coverage.contains('[') && !coverage.contains('{'));
+        assert!(coverage.contains('\u{3b1}') && !coverage.contains('\u{3c2}'));
+        assert_eq!(coverage.len(), 26 + 26 + 17);
+        assert_eq!(coverage.chars().take(3).collect::<String>(), "ABC");
+        assert_eq!(coverage.chars().last(), Some('\u{3c1}'));
+        assert!(Coverage::default().is_empty());
+        assert!(!Coverage::default().contains('\0'));
+    }
+
+    #[test]
+    fn chars_skip_surrogates() {
+        let coverage = coverage(&[0x41..0x42, 0xd7ff..0xe001]);
+
+        assert_eq!(coverage.len(), 0x803);
+        assert_eq!(
+            coverage.chars().collect::<Vec<_>>(),
+            ['A', '\u{d7ff}', '\u{e000}']
+        );
+    }
+
+    #[test]
+    fn counts_blocks_without_control_characters() {
+        let blocks = coverage(&[0x00..0x41, 0x3b1..0x3c2]).blocks();
+
+        let summary: Vec<_> = blocks
+            .iter()
+            .map(|block| (block.name, block.covered, block.total))
+            .collect();
+        // Basic Latin has 33 control characters, and Greek and Coptic has 9 unassigned code points
+        assert_eq!(
+            summary,
+            [("Basic Latin", 33, 95), ("Greek and Coptic", 17, 135)]
+        );
+        assert_eq!(blocks[0].range, 0x00..0x80);
+        assert!(coverage(&[0x00..0x20, 0x7f..0x80]).blocks().is_empty());
+    }
+
+    #[test]
+    fn counts_scripts_except_unknown() {
+        let scripts = coverage(&[0x20..0x21, 0x41..0x5b, 0x3b1..0x3c2, 0xe000..0xf900]).scripts();
+
+        let summary: Vec<_> = scripts
+            .iter()
+            .map(|script| (&script.script, script.covered))
+            .collect();
+        assert_eq!(summary, [(b"Grek", 17), (b"Latn", 26), (b"Zyyy", 1)]);
+        assert!(scripts.iter().all(|script| script.covered < script.total));
+    }
+
+    #[test]
+    fn script_totals_count_every_character() {
+        for script in coverage(&[0x41..0x42, 0x5d0..0x5d1]).scripts() {
+            let total = ('\0'..=char::MAX)
+                .filter(|c| is_counted(*c) && unicode::script(*c) == script.script)
+                .count() as u32;
+
+            assert_eq!(script.total, total);
+        }
+    }
+
+    #[test]
+    fn percent_of_empty_total_is_zero() {
+        assert_eq!(percent(0, 0), 0.0);
+        assert_eq!(percent(1, 4), 25.0);
+    }
+}
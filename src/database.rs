@@ -0,0 +1,903 @@
+use std::{
+    collections::HashSet,
+    fmt::Write as _,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    coverage::Coverage,
+    error::{Error, Result},
+    font::Font,
+    freetype,
+    info::NameId,
+};
+
+/// First line of persisted database, changed whenever the format changes
+const HEADER: &str = "# rust-freetype-harfbuzz-example font database v1";
+
+/// Extensions of files which FreeType may open as fonts
+const FONT_EXTENSIONS: &[&str] = &[
+    "ttf", "otf", "ttc", "otc", "dfont", "woff", "woff2", "pfa", "pfb", "bdf", "pcf",
+];
+
+/// Style of font, as `font-style` of CSS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+    Oblique,
+}
+
+impl FontStyle {
+    fn as_str(self) -> &'static str {
+        match self {
+            FontStyle::Normal => "normal",
+            FontStyle::Italic => "italic",
+            FontStyle::Oblique => "oblique",
+        }
+    }
+}
+
+impl FromStr for FontStyle {
+    type Err = Error;
+
+    /// Parses `normal`, `italic` or `oblique`
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "normal" => Ok(FontStyle::Normal),
+            "italic" => Ok(FontStyle::Italic),
+            "oblique" => Ok(FontStyle::Oblique),
+            _ => Err(Error::InvalidFontStyle(s.to_string())),
+        }
+    }
+}
+
+/// Generic font family of CSS, which stands for families chosen by `FontDatabase`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GenericFamily {
+    Serif,
+    SansSerif,
+    Monospace,
+    Cursive,
+    Fantasy,
+    SystemUi,
+}
+
+impl GenericFamily {
+    /// Families tried in order when the database has no other preference
+    fn default_families(self) -> &'static [&'static str] {
+        match self {
+            GenericFamily::Serif => &[
+                "DejaVu Serif",
+                "Noto Serif",
+                "Liberation Serif",
+                "Times New Roman",
+            ],
+            GenericFamily::SansSerif => &["DejaVu Sans", "Noto Sans", "Liberation Sans", "Arial"],
+            GenericFamily::Monospace => &[
+                "DejaVu Sans Mono",
+                "Noto Sans Mono",
+                "Liberation Mono",
+                "Courier New",
+            ],
+            GenericFamily::Cursive => &["Comic Neue", "Comic Sans MS", "URW Chancery L"],
+            GenericFamily::Fantasy => &["Impact", "Papyrus"],
+            GenericFamily::SystemUi => &["Cantarell", "Ubuntu", "Noto Sans", "DejaVu Sans"],
+        }
+    }
+}
+
+/// Entry of `font-family` list of CSS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FamilyName {
+    /// Family name such as `Noto Sans`, compared ignoring ASCII case
+    Name(String),
+    Generic(GenericFamily),
+}
+
+impl FromStr for FamilyName {
+    type Err = Error;
+
+    /// Parses family name as in CSS, where quoted names are never generic families
+    ///
+    /// `serif`, `sans-serif`, `monospace`, `cursive`, `fantasy` and `system-ui` are generic families.
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        for quote in ['"', '\''] {
+            if let Some(name) = s.strip_prefix(quote).and_then(|s| s.strip_suffix(quote)) {
+                return Ok(FamilyName::Name(name.to_string()));
+            }
+        }
+
+        let generic = match s.to_ascii_lowercase().as_str() {
+            "" => return Err(Error::InvalidFamily(s.to_string())),
+            "serif" => GenericFamily::Serif,
+            "sans-serif" => GenericFamily::SansSerif,
+            "monospace" => GenericFamily::Monospace,
+            "cursive" => GenericFamily::Cursive,
+            "fantasy" => GenericFamily::Fantasy,
+            "system-ui" => GenericFamily::SystemUi,
+            _ => return Ok(FamilyName::Name(s.to_string())),
+        };
+
+        Ok(FamilyName::Generic(generic))
+    }
+}
+
+/// Font wanted from `FontDatabase`, as font properties of CSS
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontQuery {
+    /// Families in order of preference, same as `font-family` of CSS
+    pub families: Vec<FamilyName>,
+    /// Weight from 1 to 1000, `400` by default
+    pub weight: f32,
+    /// Width in percent of normal width, `100` by default
+    pub stretch: f32,
+    pub style: FontStyle,
+}
+
+impl Default for FontQuery {
+    fn default() -> Self {
+        FontQuery {
+            families: Vec::new(),
+            weight: 400.0,
+            stretch: 100.0,
+            style: FontStyle::Normal,
+        }
+    }
+}
+
+/// Face indexed by `FontDatabase`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaceRecord {
+    pub path: PathBuf,
+    /// Index of the face in the file
+    pub index: u32,
+    /// Family names in every language, typographic family names first
+    pub families: Vec<String>,
+    /// Range of weight, which is wider than one value for variable fonts with `wght` axis
+    pub weight: (f32, f32),
+    /// Range of width in percent, which is wider than one value for variable fonts with `wdth` axis
+    pub stretch: (f32, f32),
+    pub style: FontStyle,
+    /// Whether every glyph has the same advance
+    pub fixed_pitch: bool,
+    /// Ranges of characters the face has glyphs for
+    pub coverage: Coverage,
+    /// Size of the file when it was indexed
+    file_size: u64,
+    /// Modification time of the file in seconds since UNIX epoch when it was indexed
+    modified: u64,
+}
+
+impl FaceRecord {
+    fn from_font(path: &Path, index: u32, file: (u64, u64), font: &Font) -> FaceRecord {
+        let info = font.info();
+        let mut families: Vec<String> = Vec::new();
+        for id in [NameId::TypographicFamily, NameId::Family] {
+            for entry in info.names.iter().filter(|entry| entry.id == id) {
+                if !families.contains(&entry.value) {
+                    families.push(entry.value.clone());
+                }
+            }
+        }
+
+        let axes = font.variation_axes();
+        let axis_range = |tag: &[u8; 4]| {
+            axes.iter()
+                .find(|axis| axis.tag == *tag)
+                .map(|axis| (axis.min, axis.max))
+        };
+        let weight = info.weight_class as f32;
+        let stretch = stretch_of_width_class(info.width_class);
+        // Bit 9 of fsSelection is OBLIQUE
+        let style = if info.is_italic() {
+            FontStyle::Italic
+        } else if info.fs_selection & (1 << 9) != 0 || info.italic_angle != 0.0 {
+            FontStyle::Oblique
+        } else {
+            FontStyle::Normal
+        };
+
+        FaceRecord {
+            path: path.to_path_buf(),
+            index,
+            families,
+            weight: axis_range(b"wght").unwrap_or((weight, weight)),
+            stretch: axis_range(b"wdth").unwrap_or((stretch, stretch)),
+            style,
+            fixed_pitch: info.fixed_pitch,
+            coverage: font.coverage(),
+            file_size: file.0,
+            modified: file.1,
+        }
+    }
+
+    fn has_family(&self, name: &str) -> bool {
+        self.families
+            .iter()
+            .any(|family| family.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Index of font faces in directories, which finds fonts as CSS does
+///
+/// Index can be saved to a file, so later scans only open files which have changed.
+#[derive(Debug, Clone, Default)]
+pub struct FontDatabase {
+    faces: Vec<FaceRecord>,
+    /// Families of generic families which are set explicitly
+    generic_families: Vec<(GenericFamily, Vec<String>)>,
+}
+
+impl FontDatabase {
+    /// Creates empty database
+    pub fn new() -> FontDatabase {
+        FontDatabase::default()
+    }
+
+    /// Loads database saved by `save`
+    pub fn load(path: impl AsRef<Path>) -> Result<FontDatabase> {
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(Error::InvalidDatabase("unknown header".to_string()));
+        }
+
+        let mut database = FontDatabase::new();
+        let mut record: Option<FaceRecord> = None;
+        for line in lines {
+            if line.is_empty() {
+                continue;
+            }
+            if line == "face" {
+                database.faces.extend(record.take());
+                record = Some(FaceRecord {
+                    path: PathBuf::new(),
+                    index: 0,
+                    families: Vec::new(),
+                    weight: (400.0, 400.0),
+                    stretch: (100.0, 100.0),
+                    style: FontStyle::Normal,
+                    fixed_pitch: false,
+                    coverage: Coverage::default(),
+                    file_size: 0,
+                    modified: 0,
+                });
+                continue;
+            }
+
+            let invalid = || Error::InvalidDatabase(line.to_string());
+            let record = record.as_mut().ok_or_else(invalid)?;
+            let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+            match key {
+                "path" => record.path = PathBuf::from(unescape(value)),
+                "index" => record.index = value.parse().map_err(|_| invalid())?,
+                "size" => record.file_size = value.parse().map_err(|_| invalid())?,
+                "modified" => record.modified = value.parse().map_err(|_| invalid())?,
+                "family" => record.families.push(unescape(value)),
+                "weight" => record.weight = parse_pair(value).ok_or_else(invalid)?,
+                "stretch" => record.stretch = parse_pair(value).ok_or_else(invalid)?,
+                "style" => record.style = value.parse().map_err(|_| invalid())?,
+                "fixed_pitch" => record.fixed_pitch = value.parse().map_err(|_| invalid())?,
+                "coverage" => record.coverage = parse_coverage(value).ok_or_else(invalid)?,
+                _ => return Err(invalid()),
+            }
+        }
+        database.faces.extend(record);
+
+        Ok(database)
+    }
+
+    /// Saves database as text, which `load` reads
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut text = String::new();
+        text.push_str(HEADER);
+        text.push('\n');
+        for face in &self.faces {
+            // Paths are indexed only if they are valid UTF-8
+            let path = face.path.to_str().unwrap_or_default();
+            let _ = writeln!(text, "\nface");
+            let _ = writeln!(text, "path={}", escape(path));
+            let _ = writeln!(text, "index={}", face.index);
+            let _ = writeln!(text, "size={}", face.file_size);
+            let _ = writeln!(text, "modified={}", face.modified);
+            for family in &face.families {
+                let _ = writeln!(text, "family={}", escape(family));
+            }
+            let _ = writeln!(text, "weight={} {}", face.weight.0, face.weight.1);
+            let _ = writeln!(text, "stretch={} {}", face.stretch.0, face.stretch.1);
+            let _ = writeln!(text, "style={}", face.style.as_str());
+            let _ = writeln!(text, "fixed_pitch={}", face.fixed_pitch);
+            let coverage: Vec<String> = face
+                .coverage
+                .ranges()
+                .iter()
+                .map(|range| format!("{:x}-{:x}", range.start, range.end - 1))
+                .collect();
+            let _ = writeln!(text, "coverage={}", coverage.join(" "));
+        }
+        fs::write(path, text)?;
+
+        Ok(())
+    }
+
+    /// Indexes fonts in `dir` and its subdirectories
+    ///
+    /// Files indexed before are opened again only if their size or modification time has changed,
+    /// and faces of files which no longer exist under `dir` are removed.
+    /// Files which cannot be opened as fonts are skipped.
+    pub fn scan_dir(&mut self, dir: impl AsRef<Path>) -> Result<()> {
+        let dir = dir.as_ref();
+        let mut seen = HashSet::new();
+        let mut visited = HashSet::new();
+        self.scan_dir_recursive(dir, &mut seen, &mut visited)?;
+
+        self.faces
+            .retain(|face| !face.path.starts_with(dir) || seen.contains(&face.path));
+
+        Ok(())
+    }
+
+    /// Indexes fonts in the usual font directories of the system and the user
+    ///
+    /// Directories which don't exist are skipped.
+    pub fn scan_system_dirs(&mut self) -> Result<()> {
+        let mut dirs = vec![
+            PathBuf::from("/usr/share/fonts"),
+            PathBuf::from("/usr/local/share/fonts"),
+            PathBuf::from("/Library/Fonts"),
+            PathBuf::from("C:\\Windows\\Fonts"),
+        ];
+        if let Some(home) = std::env::var_os("HOME") {
+            let home = PathBuf::from(home);
+            dirs.push(home.join(".local/share/fonts"));
+            dirs.push(home.join(".fonts"));
+            dirs.push(home.join("Library/Fonts"));
+        }
+
+        for dir in dirs.into_iter().filter(|dir| dir.is_dir()) {
+            self.scan_dir(dir)?;
+        }
+
+        Ok(())
+    }
+
+    fn scan_dir_recursive(
+        &mut self,
+        dir: &Path,
+        seen: &mut HashSet<PathBuf>,
+        visited: &mut HashSet<PathBuf>,
+    ) -> Result<()> {
+        // Symbolic links may point back to a parent directory
+        if !visited.insert(fs::canonicalize(dir)?) {
+            return Ok(());
+        }
+
+        let mut entries = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect::<Vec<_>>();
+        entries.sort();
+
+        for path in entries {
+            if path.is_dir() {
+                // Unreadable subdirectories are skipped like unreadable fonts
+                let _ = self.scan_dir_recursive(&path, seen, visited);
+            } else if is_font_file(&path) && path.to_str().is_some() {
+                self.scan_file(&path);
+                seen.insert(path);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Indexes faces of `path` unless they are indexed and up to date
+    fn scan_file(&mut self, path: &Path) {
+        let Ok(metadata) = fs::metadata(path) else {
+            return;
+        };
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_secs());
+        let file = (metadata.len(), modified);
+
+        let is_up_to_date =
+            |face: &FaceRecord| face.path == path && (face.file_size, face.modified) == file;
+        if self.faces.iter().any(is_up_to_date) {
+            return;
+        }
+
+        self.faces.retain(|face| face.path != path);
+        let Ok(data) = fs::read(path) else {
+            return;
+        };
+        let data: Arc<[u8]> = data.into();
+        let Ok(count) = freetype::face::FontFace::face_count(&data) else {
+            return;
+        };
+        for index in 0..count {
+            if let Ok(font) = Font::from_bytes(data.clone(), index) {
+                self.faces
+                    .push(FaceRecord::from_font(path, index, file, &font));
+            }
+        }
+    }
+
+    /// Every indexed face
+    pub fn faces(&self) -> &[FaceRecord] {
+        &self.faces
+    }
+
+    /// Sets families which `generic` stands for, tried in order
+    ///
+    /// Each generic family has common families of Linux, macOS and Windows by default.
+    pub fn set_generic_family(&mut self, generic: GenericFamily, families: Vec<String>) {
+        self.generic_families.retain(|(other, _)| *other != generic);
+        self.generic_families.push((generic, families));
+    }
+
+    /// Face matching `query` by font matching algorithm of CSS Fonts Level 4
+    ///
+    /// The first family in `query` which the database has any face of is used,
+    /// then sans-serif, and then every face the database has.
+    /// Faces of the family are then narrowed down by stretch, style and weight in order.
+    /// `None` is returned only if the database is empty.
+    pub fn query(&self, query: &FontQuery) -> Option<&FaceRecord> {
+        self.query_faces(query, |_| true)
+    }
+
+    /// Face which has glyph for `c`, matching `query` as closely as possible
+    ///
+    /// Families of `query` are tried first, and then every face the database has.
+    pub fn query_char(&self, query: &FontQuery, c: char) -> Option<&FaceRecord> {
+        self.query_faces(query, |face| face.coverage.contains(c))
+    }
+
+    /// Loads font matching `query`, with variation axes set to the wanted weight and stretch
+    pub fn select(&self, query: &FontQuery) -> Result<Font> {
+        let face = self.query(query).ok_or(Error::NoMatchingFont)?;
+
+        self.load_face(face, query)
+    }
+
+    /// Loads `face` found by `query`, with variation axes set to the wanted weight and stretch
+    ///
+    /// Values out of the range of the face are clamped.
+    pub fn load_face(&self, face: &FaceRecord, query: &FontQuery) -> Result<Font> {
+        let path = face.path.to_str().ok_or(Error::NoMatchingFont)?;
+        let mut font = Font::from_file(path, face.index)?;
+        let mut variations = Vec::new();
+        if face.weight.0 < face.weight.1 {
+            variations.push(("wght", query.weight));
+        }
+        if face.stretch.0 < face.stretch.1 {
+            variations.push(("wdth", query.stretch));
+        }
+        if !variations.is_empty() {
+            font.set_variations(&variations)?;
+        }
+
+        Ok(font)
+    }
+
+    fn query_faces(
+        &self,
+        query: &FontQuery,
+        filter: impl Fn(&FaceRecord) -> bool,
+    ) -> Option<&FaceRecord> {
+        let default_family = [FamilyName::Generic(GenericFamily::SansSerif)];
+        let families = query.families.iter().chain(default_family.iter());
+
+        families
+            .flat_map(|family| self.family_names(family))
+            .find_map(|name| {
+                let faces = self
+                    .faces
+                    .iter()
+                    .filter(|face| face.has_family(&name) && filter(face));
+
+                best_face(faces, query)
+            })
+            .or_else(|| {
+                // Any fixed-pitch face is better than no monospace face at all
+                let wants_monospace = query
+                    .families
+                    .contains(&FamilyName::Generic(GenericFamily::Monospace));
+                let faces = self
+                    .faces
+                    .iter()
+                    .filter(|face| wants_monospace && face.fixed_pitch && filter(face));
+
+                best_face(faces, query)
+            })
+            .or_else(|| best_face(self.faces.iter().filter(|face| filter(face)), query))
+    }
+
+    /// Names which `family` stands for, in order of preference
+    fn family_names(&self, family: &FamilyName) -> Vec<String> {
+        match family {
+            FamilyName::Name(name) => vec![name.clone()],
+            FamilyName::Generic(generic) => self
+                .generic_families
+                .iter()
+                .find(|(other, _)| other == generic)
+                .map(|(_, families)| families.clone())
+                .unwrap_or_else(|| {
+                    generic
+                        .default_families()
+                        .iter()
+                        .map(|name| name.to_string())
+                        .collect()
+                }),
+        }
+    }
+}
+
+/// Narrows down faces of one family by stretch, style and weight in order
+fn best_face<'a>(
+    faces: impl Iterator<Item = &'a FaceRecord>,
+    query: &FontQuery,
+) -> Option<&'a FaceRecord> {
+    let faces: Vec<&FaceRecord> = faces.collect();
+    let faces = closest(faces, |face| stretch_key(query.stretch, face.stretch));
+    let faces = closest(faces, |face| (style_key(query.style, face.style), 0.0));
+    let faces = closest(faces, |face| weight_key(query.weight, face.weight));
+
+    faces.first().copied()
+}
+
+/// Faces with the smallest key, which is a group and a distance within the group
+fn closest(faces: Vec<&FaceRecord>, key: impl Fn(&FaceRecord) -> (u8, f32)) -> Vec<&FaceRecord> {
+    let compare = |a: &(u8, f32), b: &(u8, f32)| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1));
+    let Some(best) = faces.iter().map(|face| key(face)).min_by(compare) else {
+        return faces;
+    };
+
+    faces
+        .into_iter()
+        .filter(|face| compare(&key(face), &best).is_eq())
+        .collect()
+}
+
+/// Narrower widths are preferred for condensed stretch, and wider ones otherwise
+fn stretch_key(desired: f32, (min, max): (f32, f32)) -> (u8, f32) {
+    if min <= desired && desired <= max {
+        (0, 0.0)
+    } else if (desired <= 100.0) == (max < desired) {
+        (1, (desired - max).max(min - desired))
+    } else {
+        (2, (desired - max).max(min - desired))
+    }
+}
+
+/// Italic falls back to oblique, oblique to italic, and normal to oblique
+fn style_key(desired: FontStyle, style: FontStyle) -> u8 {
+    let order = match desired {
+        FontStyle::Normal => [FontStyle::Normal, FontStyle::Oblique, FontStyle::Italic],
+        FontStyle::Italic => [FontStyle::Italic, FontStyle::Oblique, FontStyle::Normal],
+        FontStyle::Oblique => [FontStyle::Oblique, FontStyle::Italic, FontStyle::Normal],
+    };
+
+    order.iter().position(|other| *other == style).unwrap_or(3) as u8
+}
+
+/// Weights between 400 and 500 prefer heavier weights up to 500, then lighter ones,
+/// lighter weights prefer lighter ones, and heavier weights prefer heavier ones
+fn weight_key(desired: f32, (min, max): (f32, f32)) -> (u8, f32) {
+    if min <= desired && desired <= max {
+        return (0, 0.0);
+    }
+
+    let distance = (desired - max).max(min - desired);
+    let heavier = min > desired;
+    let group = if (400.0..=500.0).contains(&desired) {
+        match heavier {
+            true if min <= 500.0 => 1,
+            false => 2,
+            true => 3,
+        }
+    } else if (desired < 400.0) != heavier {
+        1
+    } else {
+        2
+    };
+
+    (group, distance)
+}
+
+/// Width in percent for `usWidthClass` of `OS/2` table
+fn stretch_of_width_class(width_class: u16) -> f32 {
+    match width_class {
+        1 => 50.0,
+        2 => 62.5,
+        3 => 75.0,
+        4 => 87.5,
+        6 => 112.5,
+        7 => 125.0,
+        8 => 150.0,
+        9 => 200.0,
+        _ => 100.0,
+    }
+}
+
+fn is_font_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            FONT_EXTENSIONS
+                .iter()
+                .any(|font_extension| extension.eq_ignore_ascii_case(font_extension))
+        })
+}
+
+/// Escapes backslashes and line breaks, which would break lines of persisted database
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+fn parse_pair(value: &str) -> Option<(f32, f32)> {
+    let (min, max) = value.split_once(' ')?;
+
+    Some((min.parse().ok()?, max.parse().ok()?))
+}
+
+/// Parses space-separated inclusive ranges of hexadecimal code points up to U+10FFFF
+fn parse_coverage(value: &str) -> Option<Coverage> {
+    let ranges = value
+        .split_whitespace()
+        .map(|range| {
+            let (start, end) = range.split_once('-')?;
+            let start = u32::from_str_radix(start, 16).ok()?;
+            let end = u32::from_str_radix(end, 16).ok()?;
+            if start > end || end > char::MAX as u32 {
+                return None;
+            }
+
+            Some(start..end.checked_add(1)?)
+        })
+        .collect::<Option<Vec<Range<u32>>>>()?;
+
+    Some(Coverage::from_ranges(ranges))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn face(family: &str, weight: f32, style: FontStyle) -> FaceRecord {
+        FaceRecord {
+            path: PathBuf::from(format!("/fonts/{family}.ttf")),
+            index: 0,
+            families: vec![family.to_string()],
+            weight: (weight, weight),
+            stretch: (100.0, 100.0),
+            style,
+            fixed_pitch: false,
+            coverage: parse_coverage("20-7e").unwrap(),
+            file_size: 0,
+            modified: 0,
+        }
+    }
+
+    fn database(faces: Vec<FaceRecord>) -> FontDatabase {
+        FontDatabase {
+            faces,
+            generic_families: Vec::new(),
+        }
+    }
+
+    fn query(families: &[&str]) -> FontQuery {
+        FontQuery {
+            families: families
+                .iter()
+                .map(|family| family.parse().unwrap())
+                .collect(),
+            ..FontQuery::default()
+        }
+    }
+
+    fn family_of<'a>(database: &'a FontDatabase, query: &FontQuery) -> Option<&'a str> {
+        database.query(query).map(|face| face.families[0].as_str())
+    }
+
+    #[test]
+    fn saved_database_loads_the_same() {
+        let mut escaped = face("Back\\slash\nNew\rLine=", 400.0, FontStyle::Italic);
+        escaped.path = PathBuf::from("/fonts/a\\b\nc.ttc");
+        escaped.index = 2;
+        escaped.families.push("Second".to_string());
+        escaped.weight = (100.0, 900.0);
+        escaped.stretch = (62.5, 112.5);
+        escaped.fixed_pitch = true;
+        escaped.coverage = Coverage::from_ranges(vec![0x20..0x7f, 0x10fffd..0x110000]);
+        escaped.file_size = 1234;
+        escaped.modified = 5678;
+        let saved = database(vec![face("Plain", 700.0, FontStyle::Normal), escaped]);
+
+        let path = std::env::temp_dir().join(format!("font-database-{}", std::process::id()));
+        saved.save(&path).unwrap();
+        let loaded = FontDatabase::load(&path);
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(loaded.unwrap().faces, saved.faces);
+    }
+
+    #[test]
+    fn escapes_line_breaks_and_backslashes() {
+        assert_eq!(escape("a\\b\nc\rd"), "a\\\\b\\nc\\rd");
+        assert_eq!(unescape("a\\\\b\\nc\\rd"), "a\\b\nc\rd");
+        assert_eq!(unescape("trailing\\"), "trailing\\");
+    }
+
+    #[test]
+    fn parses_coverage_ranges() {
+        assert_eq!(
+            parse_coverage("20-7e a0-a0").unwrap().ranges(),
+            [0x20..0x7f, 0xa0..0xa1]
+        );
+        assert_eq!(parse_coverage("").unwrap().ranges(), []);
+        assert_eq!(
+            parse_coverage("0-0 10ffff-10ffff").unwrap().ranges(),
+            [0..1, 0x10ffff..0x110000]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_coverage_ranges() {
+        assert_eq!(parse_coverage("ffffffff-ffffffff"), None);
+        assert_eq!(parse_coverage("0-110000"), None);
+        assert_eq!(parse_coverage("7e-20"), None);
+        assert_eq!(parse_coverage("20"), None);
+        assert_eq!(parse_coverage("20-7g"), None);
+    }
+
+    #[test]
+    fn weight_key_orders_as_css() {
+        // Between 400 and 500, heavier weights up to 500 come first, then lighter ones
+        assert_eq!(weight_key(400.0, (400.0, 400.0)), (0, 0.0));
+        assert_eq!(weight_key(400.0, (300.0, 500.0)), (0, 0.0));
+        assert_eq!(weight_key(400.0, (500.0, 500.0)), (1, 100.0));
+        assert_eq!(weight_key(400.0, (300.0, 300.0)), (2, 100.0));
+        assert_eq!(weight_key(400.0, (600.0, 600.0)), (3, 200.0));
+        // Lighter weights prefer lighter ones, and heavier weights prefer heavier ones
+        assert_eq!(weight_key(300.0, (200.0, 200.0)), (1, 100.0));
+        assert_eq!(weight_key(300.0, (350.0, 350.0)), (2, 50.0));
+        assert_eq!(weight_key(700.0, (800.0, 900.0)), (1, 100.0));
+        assert_eq!(weight_key(700.0, (100.0, 600.0)), (2, 100.0));
+    }
+
+    #[test]
+    fn stretch_key_orders_as_css() {
+        assert_eq!(stretch_key(100.0, (75.0, 125.0)), (0, 0.0));
+        // Normal and condensed stretches prefer narrower widths
+        assert_eq!(stretch_key(100.0, (87.5, 87.5)), (1, 12.5));
+        assert_eq!(stretch_key(100.0, (112.5, 112.5)), (2, 12.5));
+        // Expanded stretches prefer wider widths
+        assert_eq!(stretch_key(125.0, (150.0, 150.0)), (1, 25.0));
+        assert_eq!(stretch_key(125.0, (112.5, 112.5)), (2, 12.5));
+    }
+
+    #[test]
+    fn style_key_orders_as_css() {
+        use FontStyle::{Italic, Normal, Oblique};
+
+        assert_eq!(
+            [Italic, Oblique, Normal].map(|style| style_key(Italic, style)),
+            [0, 1, 2]
+        );
+        assert_eq!(
+            [Oblique, Italic, Normal].map(|style| style_key(Oblique, style)),
+            [0, 1, 2]
+        );
+        assert_eq!(
+            [Normal, Oblique, Italic].map(|style| style_key(Normal, style)),
+            [0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn narrows_family_by_style_before_weight() {
+        let database = database(vec![
+            face("Family", 400.0, FontStyle::Normal),
+            face("Family", 700.0, FontStyle::Normal),
+            face("Family", 400.0, FontStyle::Italic),
+        ]);
+        let mut bold_italic = query(&["family"]);
+        bold_italic.weight = 700.0;
+        bold_italic.style = FontStyle::Italic;
+
+        let face = database.query(&bold_italic).unwrap();
+        assert_eq!((face.weight.0, face.style), (400.0, FontStyle::Italic));
+    }
+
+    #[test]
+    fn falls_back_through_generic_families() {
+        let mut database = database(vec![
+            face("Other", 400.0, FontStyle::Normal),
+            face("Noto Serif", 400.0, FontStyle::Normal),
+            face("Noto Sans", 400.0, FontStyle::Normal),
+        ]);
+
+        assert_eq!(family_of(&database, &query(&["serif"])), Some("Noto Serif"));
+        assert_eq!(
+            family_of(&database, &query(&["'serif'"])),
+            Some("Noto Sans")
+        );
+        // Families which the database lacks fall back to sans-serif
+        assert_eq!(
+            family_of(&database, &query(&["Missing"])),
+            Some("Noto Sans")
+        );
+        assert_eq!(
+            family_of(&database, &query(&["Missing", "Noto Serif"])),
+            Some("Noto Serif")
+        );
+
+        database.set_generic_family(GenericFamily::SansSerif, vec!["OTHER".to_string()]);
+        assert_eq!(family_of(&database, &query(&["Missing"])), Some("Other"));
+    }
+
+    #[test]
+    fn falls_back_to_fixed_pitch_for_monospace() {
+        let mut fixed = face("Fixed", 400.0, FontStyle::Normal);
+        fixed.fixed_pitch = true;
+        let database = database(vec![face("Proportional", 400.0, FontStyle::Normal), fixed]);
+
+        assert_eq!(family_of(&database, &query(&["monospace"])), Some("Fixed"));
+        assert_eq!(
+            family_of(&database, &query(&["Missing"])),
+            Some("Proportional")
+        );
+        assert_eq!(
+            family_of(&FontDatabase::new(), &query(&["monospace"])),
+            None
+        );
+    }
+
+    #[test]
+    fn query_char_skips_faces_without_glyph() {
+        let mut greek = face("Greek", 400.0, FontStyle::Normal);
+        greek.coverage = parse_coverage("370-3ff").unwrap();
+        let database = database(vec![face("Noto Sans", 400.0, FontStyle::Normal), greek]);
+
+        let family = |c| {
+            database
+                .query_char(&query(&["sans-serif"]), c)
+                .map(|face| face.families[0].as_str())
+        };
+        assert_eq!(family('a'), Some("Noto Sans"));
+        assert_eq!(family('\u{3b1}'), Some("Greek"));
+        assert_eq!(family('\u{5d0}'), None);
+    }
+}
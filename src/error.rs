@@ -0,0 +1,433 @@
+use std::{ffi::NulError, fmt, io, sync::Arc};
+
+use crate::{render::RenderMode, shaping::Direction};
+
+/// Result type used throughout this crate
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Error returned by fallible font operations
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// FreeType returned non-zero error code
+    FreeType(FreeTypeError),
+    /// HarfBuzz failed to create blob from font data
+    HarfBuzzBlob,
+    /// HarfBuzz failed to create font-face from blob
+    HarfBuzzFace,
+    /// HarfBuzz failed to allocate buffer for text
+    HarfBuzzBuffer,
+    /// Font file could not be read
+    Io(Arc<io::Error>),
+    /// Text contains interior NUL byte
+    InvalidText(NulError),
+    /// Script is not ISO 15924 four-letter tag
+    InvalidScript(String),
+    /// Direction is not one of `ltr`, `rtl`, `ttb` and `btt`
+    InvalidDirection(String),
+    /// Feature string cannot be parsed
+    InvalidFeature(String),
+    /// Alignment is not one of `start`, `end`, `left`, `right`, `center` and `justify`
+    InvalidAlignment(String),
+    /// Render mode is not one of `normal`, `mono`, `lcd`, `lcd-v` and `sdf`
+    InvalidRenderMode(String),
+    /// Color is not one of `#rrggbb`, `#rrggbbaa` and `transparent`
+    InvalidColor(String),
+    /// Blending is not one of `srgb`, `linear` and `gamma:<gamma>,<contrast>`
+    InvalidBlending(String),
+    /// Hinting is not one of `none`, `light`, `normal`, `mono` and `auto`
+    InvalidHinting(String),
+    /// Variation axis tag is not one to four ASCII letters
+    InvalidVariation(String),
+    /// Font has no named instance of the name
+    UnknownNamedInstance(String),
+    /// Font family name is empty
+    InvalidFamily(String),
+    /// Font style is not one of `normal`, `italic` and `oblique`
+    InvalidFontStyle(String),
+    /// Font database has no face to match
+    NoMatchingFont,
+    /// Saved font database cannot be parsed
+    InvalidDatabase(String),
+    /// Text cannot be laid out in direction, which is bottom to top
+    UnsupportedDirection(Direction),
+    /// Shaped cluster or line is not at a character of paragraph text
+    OffsetOutOfText(usize),
+    /// Paragraph was given vertical direction, while only horizontal lines are supported
+    VerticalParagraph(Direction),
+    /// Rendered glyph has pixel mode which cannot be drawn into `StringBitmap`
+    UnsupportedPixelMode(u8),
+    /// Render mode is not supported by FreeType library loaded at runtime
+    UnsupportedRenderMode(RenderMode),
+    /// Bitmap size does not fit in memory
+    SizeOverflow,
+    /// Font stack was created without any font
+    EmptyFontStack,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::FreeType(err) => err.fmt(f),
+            Error::HarfBuzzBlob => write!(f, "HarfBuzz failed to create blob"),
+            Error::HarfBuzzFace => write!(f, "HarfBuzz failed to create face"),
+            Error::HarfBuzzBuffer => write!(f, "HarfBuzz failed to allocate buffer"),
+            Error::Io(err) => write!(f, "failed to read font: {}", err),
+            Error::InvalidText(err) => write!(f, "invalid text: {}", err),
+            Error::InvalidScript(script) => write!(f, "invalid script tag {:?}", script),
+            Error::InvalidDirection(direction) => write!(f, "invalid direction {:?}", direction),
+            Error::InvalidFeature(feature) => write!(f, "invalid feature {:?}", feature),
+            Error::InvalidAlignment(alignment) => write!(f, "invalid alignment {:?}", alignment),
+            Error::InvalidRenderMode(mode) => write!(f, "invalid render mode {:?}", mode),
+            Error::InvalidColor(color) => write!(f, "invalid color {:?}", color),
+            Error::InvalidBlending(blending) => write!(f, "invalid blending {:?}", blending),
+            Error::InvalidHinting(hinting) => write!(f, "invalid hinting {:?}", hinting),
+            Error::InvalidVariation(tag) => write!(f, "invalid variation axis tag {:?}", tag),
+            Error::UnknownNamedInstance(name) => write!(f, "unknown named instance {:?}", name),
+            Error::InvalidFamily(family) => write!(f, "invalid font family {:?}", family),
+            Error::InvalidFontStyle(style) => write!(f, "invalid font style {:?}", style),
+            Error::NoMatchingFont => write!(f, "no font matches"),
+            Error::InvalidDatabase(line) => write!(f, "invalid font database: {}", line),
+            Error::UnsupportedDirection(direction) => {
+                write!(f, "text cannot be laid out in direction {:?}", direction)
+            }
+            Error::OffsetOutOfText(offset) => {
+                write!(f, "byte offset {} is not at a character of text", offset)
+            }
+            Error::VerticalParagraph(direction) => {
+                write!(f, "paragraph does not support direction {:?}", direction)
+            }
+            Error::UnsupportedPixelMode(mode) => write!(f, "unsupported pixel mode {}", mode),
+            Error::UnsupportedRenderMode(mode) => {
+                write!(f, "render mode {:?} is not supported by FreeType", mode)
+            }
+            Error::SizeOverflow => write!(f, "bitmap size overflow"),
+            Error::EmptyFontStack => write!(f, "font stack has no font"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::FreeType(err) => Some(err),
+            Error::Io(err) => Some(err.as_ref()),
+            Error::InvalidText(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(Arc::new(err))
+    }
+}
+
+impl From<FreeTypeError> for Error {
+    fn from(err: FreeTypeError) -> Self {
+        Error::FreeType(err)
+    }
+}
+
+/// Error code returned by FreeType
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreeTypeError {
+    code: i32,
+}
+
+impl FreeTypeError {
+    pub(crate) fn new(code: i32) -> FreeTypeError {
+        FreeTypeError { code }
+    }
+
+    /// Raw `FT_Error` value
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+
+    /// Symbolic name such as `FT_Err_Cannot_Open_Resource`
+    pub fn name(&self) -> &'static str {
+        self.lookup().map_or("FT_Err_Unknown", |(_, name, _)| name)
+    }
+
+    /// Human readable message from FreeType's `fterrdef.h`
+    pub fn message(&self) -> &'static str {
+        self.lookup()
+            .map_or("unknown error", |(_, _, message)| message)
+    }
+
+    fn lookup(&self) -> Option<&'static (i32, &'static str, &'static str)> {
+        FREETYPE_ERRORS
+            .iter()
+            .find(|(code, _, _)| *code == self.code)
+    }
+}
+
+impl fmt::Display for FreeTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "FreeType error {:#04x} ({}): {}",
+            self.code,
+            self.name(),
+            self.message()
+        )
+    }
+}
+
+impl std::error::Error for FreeTypeError {}
+
+/// `FT_Err_*` codes, names and messages as listed in `fterrdef.h`
+const FREETYPE_ERRORS: &[(i32, &str, &str)] = &[
+    (0x00, "FT_Err_Ok", "no error"),
+    (0x01, "FT_Err_Cannot_Open_Resource", "cannot open resource"),
+    (0x02, "FT_Err_Unknown_File_Format", "unknown file format"),
+    (0x03, "FT_Err_Invalid_File_Format", "broken file"),
+    (0x04, "FT_Err_Invalid_Version", "invalid FreeType version"),
+    (
+        0x05,
+        "FT_Err_Lower_Module_Version",
+        "module version is too low",
+    ),
+    (0x06, "FT_Err_Invalid_Argument", "invalid argument"),
+    (
+        0x07,
+        "FT_Err_Unimplemented_Feature",
+        "unimplemented feature",
+    ),
+    (0x08, "FT_Err_Invalid_Table", "broken table"),
+    (0x09, "FT_Err_Invalid_Offset", "broken offset within table"),
+    (
+        0x0A,
+        "FT_Err_Array_Too_Large",
+        "array allocation size too large",
+    ),
+    (0x0B, "FT_Err_Missing_Module", "missing module"),
+    (0x0C, "FT_Err_Missing_Property", "missing property"),
+    (0x10, "FT_Err_Invalid_Glyph_Index", "invalid glyph index"),
+    (
+        0x11,
+        "FT_Err_Invalid_Character_Code",
+        "invalid character code",
+    ),
+    (
+        0x12,
+        "FT_Err_Invalid_Glyph_Format",
+        "unsupported glyph image format",
+    ),
+    (
+        0x13,
+        "FT_Err_Cannot_Render_Glyph",
+        "cannot render this glyph format",
+    ),
+    (0x14, "FT_Err_Invalid_Outline", "invalid outline"),
+    (0x15, "FT_Err_Invalid_Composite", "invalid composite glyph"),
+    (0x16, "FT_Err_Too_Many_Hints", "too many hints"),
+    (0x17, "FT_Err_Invalid_Pixel_Size", "invalid pixel size"),
+    (0x18, "FT_Err_Invalid_SVG_Document", "invalid SVG document"),
+    (0x20, "FT_Err_Invalid_Handle", "invalid object handle"),
+    (
+        0x21,
+        "FT_Err_Invalid_Library_Handle",
+        "invalid library handle",
+    ),
+    (
+        0x22,
+        "FT_Err_Invalid_Driver_Handle",
+        "invalid module handle",
+    ),
+    (0x23, "FT_Err_Invalid_Face_Handle", "invalid face handle"),
+    (0x24, "FT_Err_Invalid_Size_Handle", "invalid size handle"),
+    (
+        0x25,
+        "FT_Err_Invalid_Slot_Handle",
+        "invalid glyph slot handle",
+    ),
+    (
+        0x26,
+        "FT_Err_Invalid_CharMap_Handle",
+        "invalid charmap handle",
+    ),
+    (
+        0x27,
+        "FT_Err_Invalid_Cache_Handle",
+        "invalid cache manager handle",
+    ),
+    (
+        0x28,
+        "FT_Err_Invalid_Stream_Handle",
+        "invalid stream handle",
+    ),
+    (0x30, "FT_Err_Too_Many_Drivers", "too many modules"),
+    (0x31, "FT_Err_Too_Many_Extensions", "too many extensions"),
+    (0x40, "FT_Err_Out_Of_Memory", "out of memory"),
+    (0x41, "FT_Err_Unlisted_Object", "unlisted object"),
+    (0x51, "FT_Err_Cannot_Open_Stream", "cannot open stream"),
+    (0x52, "FT_Err_Invalid_Stream_Seek", "invalid stream seek"),
+    (0x53, "FT_Err_Invalid_Stream_Skip", "invalid stream skip"),
+    (0x54, "FT_Err_Invalid_Stream_Read", "invalid stream read"),
+    (
+        0x55,
+        "FT_Err_Invalid_Stream_Operation",
+        "invalid stream operation",
+    ),
+    (
+        0x56,
+        "FT_Err_Invalid_Frame_Operation",
+        "invalid frame operation",
+    ),
+    (0x57, "FT_Err_Nested_Frame_Access", "nested frame access"),
+    (0x58, "FT_Err_Invalid_Frame_Read", "invalid frame read"),
+    (0x60, "FT_Err_Raster_Uninitialized", "raster uninitialized"),
+    (0x61, "FT_Err_Raster_Corrupted", "raster corrupted"),
+    (0x62, "FT_Err_Raster_Overflow", "raster overflow"),
+    (
+        0x63,
+        "FT_Err_Raster_Negative_Height",
+        "negative height while rastering",
+    ),
+    (0x70, "FT_Err_Too_Many_Caches", "too many registered caches"),
+    (0x80, "FT_Err_Invalid_Opcode", "invalid opcode"),
+    (0x81, "FT_Err_Too_Few_Arguments", "too few arguments"),
+    (0x82, "FT_Err_Stack_Overflow", "stack overflow"),
+    (0x83, "FT_Err_Code_Overflow", "code overflow"),
+    (0x84, "FT_Err_Bad_Argument", "bad argument"),
+    (0x85, "FT_Err_Divide_By_Zero", "division by zero"),
+    (0x86, "FT_Err_Invalid_Reference", "invalid reference"),
+    (0x87, "FT_Err_Debug_OpCode", "found debug opcode"),
+    (
+        0x88,
+        "FT_Err_ENDF_In_Exec_Stream",
+        "found ENDF opcode in execution stream",
+    ),
+    (0x89, "FT_Err_Nested_DEFS", "nested DEFS"),
+    (0x8A, "FT_Err_Invalid_CodeRange", "invalid code range"),
+    (
+        0x8B,
+        "FT_Err_Execution_Too_Long",
+        "execution context too long",
+    ),
+    (
+        0x8C,
+        "FT_Err_Too_Many_Function_Defs",
+        "too many function definitions",
+    ),
+    (
+        0x8D,
+        "FT_Err_Too_Many_Instruction_Defs",
+        "too many instruction definitions",
+    ),
+    (0x8E, "FT_Err_Table_Missing", "SFNT font table missing"),
+    (
+        0x8F,
+        "FT_Err_Horiz_Header_Missing",
+        "horizontal header (hhea) table missing",
+    ),
+    (
+        0x90,
+        "FT_Err_Locations_Missing",
+        "locations (loca) table missing",
+    ),
+    (0x91, "FT_Err_Name_Table_Missing", "name table missing"),
+    (
+        0x92,
+        "FT_Err_CMap_Table_Missing",
+        "character map (cmap) table missing",
+    ),
+    (
+        0x93,
+        "FT_Err_Hmtx_Table_Missing",
+        "horizontal metrics (hmtx) table missing",
+    ),
+    (
+        0x94,
+        "FT_Err_Post_Table_Missing",
+        "PostScript (post) table missing",
+    ),
+    (
+        0x95,
+        "FT_Err_Invalid_Horiz_Metrics",
+        "invalid horizontal metrics",
+    ),
+    (
+        0x96,
+        "FT_Err_Invalid_CharMap_Format",
+        "invalid character map (cmap) format",
+    ),
+    (0x97, "FT_Err_Invalid_PPem", "invalid ppem value"),
+    (
+        0x98,
+        "FT_Err_Invalid_Vert_Metrics",
+        "invalid vertical metrics",
+    ),
+    (
+        0x99,
+        "FT_Err_Could_Not_Find_Context",
+        "could not find context",
+    ),
+    (
+        0x9A,
+        "FT_Err_Invalid_Post_Table_Format",
+        "invalid PostScript (post) table format",
+    ),
+    (
+        0x9B,
+        "FT_Err_Invalid_Post_Table",
+        "invalid PostScript (post) table",
+    ),
+    (
+        0x9C,
+        "FT_Err_DEF_In_Glyf_Bytecode",
+        "found FDEF or IDEF opcode in glyf bytecode",
+    ),
+    (0x9D, "FT_Err_Missing_Bitmap", "missing bitmap in strike"),
+    (
+        0x9E,
+        "FT_Err_Missing_SVG_Hooks",
+        "SVG hooks have not been set",
+    ),
+    (0xA0, "FT_Err_Syntax_Error", "opcode syntax error"),
+    (0xA1, "FT_Err_Stack_Underflow", "argument stack underflow"),
+    (0xA2, "FT_Err_Ignore", "ignore"),
+    (
+        0xA3,
+        "FT_Err_No_Unicode_Glyph_Name",
+        "no Unicode glyph name found",
+    ),
+    (0xA4, "FT_Err_Glyph_Too_Big", "glyph too big for hinting"),
+    (
+        0xB0,
+        "FT_Err_Missing_Startfont_Field",
+        "'STARTFONT' field missing",
+    ),
+    (0xB1, "FT_Err_Missing_Font_Field", "'FONT' field missing"),
+    (0xB2, "FT_Err_Missing_Size_Field", "'SIZE' field missing"),
+    (
+        0xB3,
+        "FT_Err_Missing_Fontboundingbox_Field",
+        "'FONTBOUNDINGBOX' field missing",
+    ),
+    (0xB4, "FT_Err_Missing_Chars_Field", "'CHARS' field missing"),
+    (
+        0xB5,
+        "FT_Err_Missing_Startchar_Field",
+        "'STARTCHAR' field missing",
+    ),
+    (
+        0xB6,
+        "FT_Err_Missing_Encoding_Field",
+        "'ENCODING' field missing",
+    ),
+    (0xB7, "FT_Err_Missing_Bbx_Field", "'BBX' field missing"),
+    (0xB8, "FT_Err_Bbx_Too_Big", "'BBX' too big"),
+    (
+        0xB9,
+        "FT_Err_Corrupted_Font_Header",
+        "Font header corrupted or missing fields",
+    ),
+    (
+        0xBA,
+        "FT_Err_Corrupted_Font_Glyphs",
+        "Font glyphs corrupted or missing fields",
+    ),
+];
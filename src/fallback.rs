@@ -0,0 +1,278 @@
+use std::{collections::HashSet, ops::Range};
+
+use crate::{
+    bitmap::{StringBitmap, StringBitmapSize},
+    error::{Error, Result},
+    font::Font,
+    harfbuzz::shape::Shape,
+    itemize,
+    metrics::TextMetrics,
+    render::{Hinting, RenderMode, TextStyle},
+    shaping::{Direction, ShapingOptions},
+};
+
+/// Ordered list of fonts, where characters missing in one font
+/// are taken from the next font which covers them
+///
+/// # Notes
+/// - The first font is the primary font and decides where fallback is needed.
+/// - If no font covers a character, `.notdef` glyph of the last font is rendered.
+#[derive(Clone)]
+pub struct FontStack {
+    fonts: Vec<Font>,
+}
+
+/// Glyphs shaped with one font of the stack
+struct FontRun {
+    font_index: usize,
+    shapes: Vec<Shape>,
+}
+
+impl FontStack {
+    /// Creates font stack from fonts in order of preference
+    pub fn new(fonts: Vec<Font>) -> Result<FontStack> {
+        if fonts.is_empty() {
+            return Err(Error::EmptyFontStack);
+        }
+
+        Ok(FontStack { fonts })
+    }
+
+    /// Fonts in order of preference
+    pub fn fonts(&self) -> &[Font] {
+        &self.fonts
+    }
+
+    /// Fonts in order of preference, e.g. for setting per-font features
+    pub fn fonts_mut(&mut self) -> &mut [Font] {
+        &mut self.fonts
+    }
+
+    /// Renders text, mixing glyphs from every font on one baseline
+    ///
+    /// Vertical text is mixed on one center line instead.
+    pub fn render(&mut self, text: &str, options: &ShapingOptions) -> Result<StringBitmap> {
+        let vertical = is_vertical(options);
+        let runs = self.shape(text, options)?;
+        let size = self.measure_runs(&runs, vertical)?.bitmap_size();
+
+        // Background of the primary font fills the whole bitmap
+        let mut result = StringBitmap::new(size, self.fonts[0].freetype_font.style())?;
+        let (origin_x, origin_y) = (size.x_min as i64, size.y_max as i64);
+        let mut pen = if vertical {
+            origin_y << 6
+        } else {
+            origin_x << 6
+        };
+        for run in &runs {
+            let face = &mut self.fonts[run.font_index].freetype_font;
+            pen = if vertical {
+                face.draw_vertical_string(&run.shapes, &mut result, origin_x, pen)?
+            } else {
+                face.draw_string(&run.shapes, &mut result, pen, origin_y)?
+            };
+        }
+
+        Ok(result)
+    }
+
+    /// Measures size of rendered text
+    ///
+    /// `options` must be same with the one passed to `render`.
+    pub fn measure_size(
+        &mut self,
+        text: &str,
+        options: &ShapingOptions,
+    ) -> Result<StringBitmapSize> {
+        Ok(self.measure(text, options)?.bitmap_size())
+    }
+
+    /// Measures advance, logical and ink boxes and glyph positions of text
+    ///
+    /// Logical box covers ascender and descender of the primary font and every fallback font used.
+    pub fn measure(&mut self, text: &str, options: &ShapingOptions) -> Result<TextMetrics> {
+        let runs = self.shape(text, options)?;
+
+        self.measure_runs(&runs, is_vertical(options))
+    }
+
+    fn measure_runs(&mut self, runs: &[FontRun], vertical: bool) -> Result<TextMetrics> {
+        let mut metrics = self.fonts[0].freetype_font.text_metrics(&[], vertical)?;
+        for run in runs {
+            let run_metrics = self.fonts[run.font_index]
+                .freetype_font
+                .text_metrics(&run.shapes, vertical)?;
+            metrics = metrics.join(run_metrics);
+        }
+
+        Ok(metrics)
+    }
+
+    /// Shapes text into runs of glyphs in visual order
+    fn shape(&self, text: &str, options: &ShapingOptions) -> Result<Vec<FontRun>> {
+        let mut runs = Vec::new();
+        for (range, run_options) in itemize::runs(text, options) {
+            self.shape_with_fallback(text, range, &run_options, 0, &mut runs)?;
+        }
+
+        Ok(runs)
+    }
+
+    /// Shapes `range` of text with `font_index`-th font,
+    /// then re-shapes clusters which got `.notdef` glyph with the following fonts
+    fn shape_with_fallback(
+        &self,
+        text: &str,
+        range: Range<usize>,
+        options: &ShapingOptions,
+        font_index: usize,
+        runs: &mut Vec<FontRun>,
+    ) -> Result<()> {
+        let shapes = self.fonts[font_index].shape_run(text, range.clone(), options)?;
+        let missing_clusters: HashSet<u32> = shapes
+            .iter()
+            .filter(|shape| shape.glyph_id == 0)
+            .map(|shape| shape.cluster)
+            .collect();
+
+        if missing_clusters.is_empty() || font_index + 1 == self.fonts.len() {
+            push_run(runs, font_index, shapes);
+            return Ok(());
+        }
+
+        // Cluster values are byte offsets, so each cluster spans until the next one
+        let mut boundaries: Vec<usize> =
+            shapes.iter().map(|shape| shape.cluster as usize).collect();
+        boundaries.push(range.end);
+        boundaries.sort_unstable();
+        boundaries.dedup();
+        let cluster_end = |cluster: u32| {
+            let index = boundaries.partition_point(|boundary| *boundary <= cluster as usize);
+            boundaries[index]
+        };
+
+        let mut covered = Vec::new();
+        let mut shapes = shapes.into_iter().peekable();
+        while let Some(shape) = shapes.next() {
+            if !missing_clusters.contains(&shape.cluster) {
+                covered.push(shape);
+                continue;
+            }
+
+            // Glyphs of missing clusters next to each other are re-shaped together
+            let mut start = shape.cluster as usize;
+            let mut end = cluster_end(shape.cluster);
+            while let Some(next) = shapes.next_if(|next| missing_clusters.contains(&next.cluster)) {
+                start = std::cmp::min(start, next.cluster as usize);
+                end = std::cmp::max(end, cluster_end(next.cluster));
+            }
+
+            push_run(runs, font_index, std::mem::take(&mut covered));
+            self.shape_with_fallback(text, start..end, options, font_index + 1, runs)?;
+        }
+        push_run(runs, font_index, covered);
+
+        Ok(())
+    }
+
+    pub fn set_dpi(&mut self, hdpi: u32, vdpi: u32) {
+        for font in &mut self.fonts {
+            font.set_dpi(hdpi, vdpi);
+        }
+    }
+
+    pub fn set_font_size(&mut self, pt: f32) -> Result<()> {
+        for font in &mut self.fonts {
+            font.set_font_size(pt)?;
+        }
+
+        Ok(())
+    }
+
+    /// More bigger value, less space between letters
+    ///
+    /// Default value is 1.2
+    pub fn set_letter_spacing(&mut self, spacing: f64) {
+        for font in &mut self.fonts {
+            font.set_letter_spacing(spacing);
+        }
+    }
+
+    /// Sets how glyphs are rasterized, `RenderMode::Lcd` by default
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        for font in &mut self.fonts {
+            font.set_render_mode(render_mode);
+        }
+    }
+
+    /// Sets colors of rendered text, white text on transparent background by default
+    pub fn set_style(&mut self, style: TextStyle) {
+        for font in &mut self.fonts {
+            font.set_style(style);
+        }
+    }
+
+    /// Sets how outlines are fitted to the pixel grid, `Hinting::Normal` by default
+    pub fn set_hinting(&mut self, hinting: Hinting) {
+        for font in &mut self.fonts {
+            font.set_hinting(hinting);
+        }
+    }
+
+    /// Sets number of fractional positions a glyph can be rendered at within a pixel
+    ///
+    /// Default value is 4, and `1` snaps every glyph to whole pixels.
+    pub fn set_subpixel_bins(&mut self, bins: u8) {
+        for font in &mut self.fonts {
+            font.set_subpixel_bins(bins);
+        }
+    }
+
+    /// Sets whether embedded bitmaps are preferred to outlines, `false` by default
+    pub fn set_embedded_bitmaps(&mut self, embedded_bitmaps: bool) {
+        for font in &mut self.fonts {
+            font.set_embedded_bitmaps(embedded_bitmaps);
+        }
+    }
+
+    /// Sets index of palette which color glyphs are painted with, `0` by default
+    pub fn set_palette(&mut self, palette_index: u16) {
+        for font in &mut self.fonts {
+            font.set_palette(palette_index);
+        }
+    }
+
+    /// Sets design coordinates of variation axes of every font
+    ///
+    /// Each font ignores tags of axes it doesn't have.
+    pub fn set_variations(&mut self, variations: &[(&str, f32)]) -> Result<()> {
+        for font in &mut self.fonts {
+            font.set_variations(variations)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets whether `opsz` axis of every font follows the font size, `true` by default
+    pub fn set_optical_sizing(&mut self, optical_sizing: bool) {
+        for font in &mut self.fonts {
+            font.set_optical_sizing(optical_sizing);
+        }
+    }
+}
+
+fn is_vertical(options: &ShapingOptions) -> bool {
+    options.direction.is_some_and(Direction::is_vertical)
+}
+
+/// Appends glyphs to the last run if it comes from the same font
+fn push_run(runs: &mut Vec<FontRun>, font_index: usize, shapes: Vec<Shape>) {
+    if shapes.is_empty() {
+        return;
+    }
+
+    match runs.last_mut() {
+        Some(last) if last.font_index == font_index => last.shapes.extend(shapes),
+        _ => runs.push(FontRun { font_index, shapes }),
+    }
+}
@@ -1,47 +1,232 @@
+use std::{ops::Range, sync::Arc};
+
 use crate::{
     bitmap::{StringBitmap, StringBitmapSize},
+    coverage::Coverage,
+    error::{Error, Result},
     freetype,
-    harfbuzz::{self, buffer, shape},
+    harfbuzz::{
+        self, buffer,
+        shape::{self, Orientation, Shape},
+    },
+    info::{CollectionFace, FontInfo, NameId},
+    itemize,
+    metrics::{FontMetrics, TextMetrics},
+    render::{Hinting, RenderMode, TextStyle},
+    shaping::{Direction, Feature, ShapingOptions},
+    variation::{self, NamedInstance, VariationAxis},
 };
 
 #[derive(Clone)]
 pub struct Font {
     harfbuzz_font: harfbuzz::font::Font,
-    freetype_font: freetype::face::FontFace,
+    pub(crate) freetype_font: freetype::face::FontFace,
+    /// Features applied to every render call
+    features: Vec<Feature>,
+    /// Design coordinates of variation axes given by `set_variations` or `set_named_instance`
+    variations: Vec<([u8; 4], f32)>,
+    /// Whether `opsz` axis follows the font size unless given in `variations`
+    optical_sizing: bool,
 }
 
 impl Font {
-    pub fn from_file(filename: &str, index: u32) -> Font {
-        Font {
-            harfbuzz_font: harfbuzz::font::Font::new(filename, index),
-            freetype_font: freetype::face::FontFace::from_file(filename, index as i64)
-                .expect("Failed to load font with FreeType"),
-        }
+    /// Loads font from file
+    ///
+    /// The file is read only once and shared by FreeType and HarfBuzz.
+    pub fn from_file(filename: &str, index: u32) -> Result<Font> {
+        let data: Arc<[u8]> = std::fs::read(filename)?.into();
+
+        Font::from_bytes(data, index)
+    }
+
+    /// Loads font from font data in memory
+    ///
+    /// `data` is kept alive as long as this font or any of its clones exists.
+    pub fn from_bytes(data: Arc<[u8]>, index: u32) -> Result<Font> {
+        // FreeType goes first since its error codes tell more about what went wrong
+        let freetype_font = freetype::face::FontFace::from_bytes(data.clone(), index)?;
+        let harfbuzz_font =
+            harfbuzz::font::Font::from_bytes(&data, index, freetype_font.raw_ptr())?;
+
+        let mut font = Font {
+            harfbuzz_font,
+            freetype_font,
+            features: Vec::new(),
+            variations: Vec::new(),
+            optical_sizing: true,
+        };
+        font.apply_variations();
+
+        Ok(font)
+    }
+
+    /// Lists every face in font file with its index, which `from_file` takes
+    ///
+    /// Collections such as TTC, OTC and DFONT have more than one face.
+    pub fn faces_in_file(filename: &str) -> Result<Vec<CollectionFace>> {
+        let data: Arc<[u8]> = std::fs::read(filename)?.into();
+
+        Font::faces_in_bytes(data)
+    }
+
+    /// Lists every face in font data in memory with its index, which `from_bytes` takes
+    pub fn faces_in_bytes(data: Arc<[u8]>) -> Result<Vec<CollectionFace>> {
+        let count = freetype::face::FontFace::face_count(&data)?;
+
+        (0..count)
+            .map(|index| {
+                let font = Font::from_bytes(data.clone(), index)?;
+                let info = font.info();
+                // Typographic names group all styles of variable and large families
+                let name = |typographic, fallback| {
+                    info.name(typographic)
+                        .or_else(|| info.name(fallback))
+                        .unwrap_or_default()
+                        .to_string()
+                };
+
+                Ok(CollectionFace {
+                    index,
+                    family: name(NameId::TypographicFamily, NameId::Family),
+                    style: name(NameId::TypographicSubfamily, NameId::Subfamily),
+                    named_instances: font.named_instances(),
+                })
+            })
+            .collect()
     }
 
-    pub fn render(&mut self, text: &str) -> Result<StringBitmap, i32> {
-        let buffer = buffer::Buffer::new(text);
-        let shapes = shape::shape(buffer, &self.harfbuzz_font);
-        println!("{:#?}", shapes);
+    /// Renders text
+    ///
+    /// Script, language and direction not given in `options` are guessed from `text`.
+    pub fn render(&mut self, text: &str, options: &ShapingOptions) -> Result<StringBitmap> {
+        let shapes = self.shape(text, options)?;
 
         self.freetype_font.render_string(shapes.as_slice())
     }
 
-    pub fn measure_size(&mut self, text: &str) -> Result<StringBitmapSize, i32> {
-        let buffer = buffer::Buffer::new(text);
-        let shapes = shape::shape(buffer, &self.harfbuzz_font);
+    /// Measures size of rendered text
+    ///
+    /// `options` must be same with the one passed to `render`.
+    pub fn measure_size(
+        &mut self,
+        text: &str,
+        options: &ShapingOptions,
+    ) -> Result<StringBitmapSize> {
+        Ok(self.measure(text, options)?.bitmap_size())
+    }
+
+    /// Measures advance, logical and ink boxes and glyph positions of text
+    ///
+    /// `options` must be same with the one passed to `render` for the bitmap to match.
+    pub fn measure(&mut self, text: &str, options: &ShapingOptions) -> Result<TextMetrics> {
+        let shapes = self.shape(text, options)?;
+        let vertical = options.direction.is_some_and(Direction::is_vertical);
 
-        self.freetype_font.measure_size(shapes.as_slice())
+        self.freetype_font.text_metrics(&shapes, vertical)
+    }
+
+    /// Ascender, descender, line gap and other metrics at the current size
+    ///
+    /// `render` and `measure_size` reserve ascender and descender on every string,
+    /// so that strings rendered separately share the same baseline.
+    pub fn metrics(&mut self) -> Result<FontMetrics> {
+        self.freetype_font.metrics()
+    }
+
+    /// Shapes text into glyphs in visual order
+    ///
+    /// Text is itemized into runs by direction and script,
+    /// and every run is shaped separately.
+    fn shape(&self, text: &str, options: &ShapingOptions) -> Result<Vec<Shape>> {
+        let mut shapes = Vec::new();
+        for (range, run_options) in itemize::runs(text, options) {
+            shapes.extend(self.shape_run(text, range, &run_options)?);
+        }
+
+        Ok(shapes)
+    }
+
+    /// Shapes `range` of text as one run, using the rest of text as context
+    ///
+    /// Sideways runs of vertical text are shaped horizontally.
+    /// Bottom-to-top text is an error, since vertical lines are laid out from top to bottom.
+    pub(crate) fn shape_run(
+        &self,
+        text: &str,
+        range: Range<usize>,
+        options: &ShapingOptions,
+    ) -> Result<Vec<Shape>> {
+        if options.direction == Some(Direction::BottomToTop) {
+            return Err(Error::UnsupportedDirection(Direction::BottomToTop));
+        }
+        let orientation = itemize::orientation(text, range.clone(), options);
+        let options = match orientation {
+            Orientation::Sideways => ShapingOptions {
+                direction: Some(Direction::LeftToRight),
+                ..options.clone()
+            },
+            _ => options.clone(),
+        };
+        let buffer = buffer::Buffer::new(text, range, &options)?;
+        let features = self.features_for(&options, orientation);
+
+        if self.freetype_font.is_hinted() || self.harfbuzz_font.needs_freetype() {
+            // Advances come from hinted outlines, so they match rendered glyphs.
+            // Bitmap-only fonts have nothing for HarfBuzz to read but FreeType glyphs.
+            let load_flags = self.freetype_font.load_flags();
+            self.freetype_font.with_face(|| {
+                shape::shape(
+                    buffer,
+                    &self.harfbuzz_font,
+                    &features,
+                    orientation,
+                    Some(load_flags),
+                )
+            })
+        } else {
+            Ok(shape::shape(
+                buffer,
+                &self.harfbuzz_font,
+                &features,
+                orientation,
+                None,
+            ))
+        }
+    }
+
+    /// Sets default OpenType features applied to every render call
+    ///
+    /// Features in `ShapingOptions` are applied after these,
+    /// so they take precedence.
+    pub fn set_features(&mut self, features: Vec<Feature>) {
+        self.features = features;
+    }
+
+    fn features_for(&self, options: &ShapingOptions, orientation: Orientation) -> Vec<Feature> {
+        let mut features = Vec::new();
+        if orientation == Orientation::Upright {
+            // Vertical alternates, which can still be turned off by other features
+            features.push(Feature::new(*b"vert", 1));
+            features.push(Feature::new(*b"vrt2", 1));
+        }
+        features.extend_from_slice(&self.features);
+        features.extend_from_slice(&options.features);
+
+        features
     }
 
     pub fn set_dpi(&mut self, hdpi: u32, vdpi: u32) {
         self.freetype_font.set_dpi(hdpi, vdpi);
     }
 
-    pub fn set_font_size(&mut self, pt: f32) {
+    /// Sets font size in pt, which `opsz` axis of variable fonts follows by default
+    pub fn set_font_size(&mut self, pt: f32) -> Result<()> {
         self.freetype_font.set_font_size(pt);
-        let (x_ppem, y_ppem) = self.freetype_font.get_ppem().expect("Failed to get ppem");
+        self.apply_variations();
+        let (x_ppem, y_ppem) = self.freetype_font.get_ppem()?;
         self.harfbuzz_font.set_ppem(x_ppem, y_ppem);
+
+        Ok(())
     }
 
     /// More bigger value, less space between letters
@@ -50,4 +235,151 @@ impl Font {
     pub fn set_letter_spacing(&mut self, spacing: f64) {
         self.freetype_font.set_letter_spacing(spacing);
     }
+
+    /// Sets how glyphs are rasterized, `RenderMode::Lcd` by default
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.freetype_font.set_render_mode(render_mode);
+    }
+
+    /// Sets colors of rendered text, white text on transparent background by default
+    pub fn set_style(&mut self, style: TextStyle) {
+        self.freetype_font.set_style(style);
+    }
+
+    /// Sets how outlines are fitted to the pixel grid, `Hinting::Normal` by default
+    ///
+    /// Unless hinting is `Hinting::None`, shaping takes advances from hinted outlines.
+    pub fn set_hinting(&mut self, hinting: Hinting) {
+        self.freetype_font.set_hinting(hinting);
+    }
+
+    /// Sets number of fractional positions a glyph can be rendered at within a pixel
+    ///
+    /// Default value is 4, and `1` snaps every glyph to whole pixels.
+    pub fn set_subpixel_bins(&mut self, bins: u8) {
+        self.freetype_font.set_subpixel_bins(bins);
+    }
+
+    /// Sets whether embedded bitmaps are preferred to outlines, `false` by default
+    ///
+    /// Color bitmap fonts such as CBDT emoji are scaled to the font size.
+    /// Bitmap-only fonts are always drawn from their bitmaps.
+    pub fn set_embedded_bitmaps(&mut self, embedded_bitmaps: bool) {
+        self.freetype_font.set_embedded_bitmaps(embedded_bitmaps);
+    }
+
+    /// Sets index of palette which color glyphs are painted with, `0` by default
+    ///
+    /// Colors standing for the text color are painted with foreground of the style.
+    pub fn set_palette(&mut self, palette_index: u16) {
+        self.freetype_font.set_palette(palette_index);
+    }
+
+    /// Sets design coordinates of variation axes, such as `[("wght", 650.0), ("wdth", 75.0)]`
+    ///
+    /// Coordinates are clamped to the range of each axis, and axes not given are set to their defaults.
+    /// Tags of axes the font doesn't have are ignored, while malformed tags are errors.
+    /// Both shaping and rasterization use the coordinates.
+    ///
+    /// `opsz` given here overrides optical size following the font size.
+    pub fn set_variations(&mut self, variations: &[(&str, f32)]) -> Result<()> {
+        self.variations = variations
+            .iter()
+            .map(|(tag, value)| Ok((variation::parse_tag(tag)?, *value)))
+            .collect::<Result<Vec<_>>>()?;
+        self.apply_variations();
+
+        Ok(())
+    }
+
+    /// Selects named instance by subfamily name such as `Bold`, ignoring ASCII case
+    ///
+    /// Coordinates of the instance replace those of `set_variations`, including `opsz`.
+    pub fn set_named_instance(&mut self, name: &str) -> Result<()> {
+        let axes = self.variation_axes();
+        let instance = self
+            .named_instances()
+            .into_iter()
+            .find(|instance| instance.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| Error::UnknownNamedInstance(name.to_string()))?;
+        self.variations = axes
+            .iter()
+            .map(|axis| axis.tag)
+            .zip(instance.coordinates)
+            .collect();
+        self.apply_variations();
+
+        Ok(())
+    }
+
+    /// Sets whether `opsz` axis follows the font size in pt, `true` by default
+    ///
+    /// Same as `font-optical-sizing` of CSS, where `false` leaves the axis at its default.
+    pub fn set_optical_sizing(&mut self, optical_sizing: bool) {
+        self.optical_sizing = optical_sizing;
+        self.apply_variations();
+    }
+
+    /// Names, style and OpenType layout scripts of the font
+    pub fn info(&self) -> FontInfo {
+        let names = self.harfbuzz_font.names();
+        let info = self.freetype_font.info();
+
+        FontInfo {
+            // Names known to FreeType for fonts without `name` table
+            names: if names.is_empty() { info.names } else { names },
+            scripts: self.harfbuzz_font.layout_scripts(),
+            ..info
+        }
+    }
+
+    /// Whether the font has glyph for `c`
+    pub fn has_char(&self, c: char) -> bool {
+        self.glyph_index(c).is_some()
+    }
+
+    /// Glyph ID of `c` in the Unicode charmap, `None` if the font has no glyph for it
+    ///
+    /// Shaping may still substitute the glyph by OpenType features.
+    pub fn glyph_index(&self, c: char) -> Option<u32> {
+        self.freetype_font.glyph_index(c)
+    }
+
+    /// Every character the font has glyph for
+    pub fn coverage(&self) -> Coverage {
+        Coverage::from_ranges(self.freetype_font.char_ranges())
+    }
+
+    /// Variation axes of the font, empty unless it is a variable font
+    pub fn variation_axes(&self) -> Vec<VariationAxis> {
+        self.harfbuzz_font.axes()
+    }
+
+    /// Named instances of the font, empty unless it is a variable font
+    pub fn named_instances(&self) -> Vec<NamedInstance> {
+        self.harfbuzz_font.named_instances()
+    }
+
+    /// Sets coordinates of every axis on both FreeType face and HarfBuzz font,
+    /// and metrics of the face which HarfBuzz varies
+    fn apply_variations(&mut self) {
+        let axes = self.variation_axes();
+        if axes.is_empty() {
+            return;
+        }
+
+        let mut variations = Vec::new();
+        if self.optical_sizing {
+            // Comes first, so `opsz` of `variations` wins
+            variations.push((*b"opsz", self.freetype_font.font_size()));
+        }
+        variations.extend_from_slice(&self.variations);
+
+        let coordinates = variation::design_coordinates(&axes, &variations);
+        self.freetype_font.set_variations(&coordinates);
+        self.harfbuzz_font.set_variations(coordinates);
+        let harfbuzz_font = &self.harfbuzz_font;
+        self.freetype_font
+            .set_varied_metrics(|tag| harfbuzz_font.metric(tag));
+    }
 }
@@ -1,2 +1,25 @@
+/// Handy macro for producing `Err` while handling FreeType error code
+///
+/// ## Usage
+///  - `error_if_not_zero(err)` : Returns `Err(Error::FreeType(err))` if `err` is not zero, otherwise returns `Ok()`
+///  - `error_if_not_zero(err, ok_value)` : Returns `Err(Error::FreeType(err))` if `err` is not zero, otherwise returns `Ok(ok_value)`
+macro_rules! error_if_not_zero {
+    ($error_code: expr) => {{
+        if $error_code != 0 {
+            Err(Error::FreeType(FreeTypeError::new($error_code)))
+        } else {
+            Ok(())
+        }
+    }};
+    ($error_code: expr, $ok_value: expr) => {{
+        if $error_code != 0 {
+            Err(Error::FreeType(FreeTypeError::new($error_code)))
+        } else {
+            Ok($ok_value)
+        }
+    }};
+}
+
+mod colr;
 pub mod face;
 mod init;
@@ -0,0 +1,1615 @@
+//! Painting of color glyphs in `COLR` table with colors of `CPAL` table
+//!
+//! Version 0 glyphs are layers of glyphs filled with solid colors,
+//! and version 1 glyphs are graphs of paints with gradients, transforms and composites.
+//! Outlines are loaded in font units and transformed while painting,
+//! as FreeType suggests for paints which are also given in font units.
+//!
+//! Types follow FreeType 2.13, except for color lines of gradients which have
+//! another layout in FreeType 2.11 and 2.12 and are read by the runtime version.
+//! Version 1 functions are looked up at runtime, so FreeType older than 2.11.1
+//! paints only version 0 glyphs.
+// Types of FreeType API missing in the bindings keep their names
+#![allow(non_camel_case_types)]
+
+use std::{ffi::c_void, sync::OnceLock};
+
+use freetype::freetype::{
+    FT_BBox, FT_Bitmap, FT_Error, FT_Face, FT_Fixed, FT_Glyph_Format, FT_Library, FT_Load_Glyph,
+    FT_Outline_Get_Bitmap, FT_Outline_Get_CBox, FT_Pixel_Mode_, FT_Pos, FT_Vector,
+    FT_LOAD_NO_BITMAP, FT_LOAD_NO_SCALE,
+};
+
+use crate::{
+    error::{Error, FreeTypeError, Result},
+    render::Color,
+};
+
+use super::init::library_version;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_Color {
+    blue: u8,
+    green: u8,
+    red: u8,
+    alpha: u8,
+}
+
+#[repr(C)]
+struct FT_Palette_Data {
+    num_palettes: u16,
+    palette_name_ids: *const u16,
+    palette_flags: *const u16,
+    num_palette_entries: u16,
+    palette_entry_name_ids: *const u16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_LayerIterator {
+    num_layers: u32,
+    layer: u32,
+    p: *mut u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_ColorStopIterator {
+    num_color_stops: u32,
+    current_color_stop: u32,
+    p: *mut u8,
+    read_variable: u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_ColorIndex {
+    palette_index: u16,
+    alpha: i16,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_ColorStop {
+    stop_offset: FT_Fixed,
+    color: FT_ColorIndex,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_ColorLine {
+    extend: u32,
+    color_stop_iterator: FT_ColorStopIterator,
+}
+
+/// `FT_ColorStopIterator` of FreeType 2.11 and 2.12, without `read_variable`
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_ColorStopIterator_2_12 {
+    num_color_stops: u32,
+    current_color_stop: u32,
+    p: *mut u8,
+}
+
+/// `FT_ColorStop` of FreeType 2.11 and 2.12, whose offset is F2DOT14
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_ColorStop_2_12 {
+    stop_offset: i16,
+    color: FT_ColorIndex,
+}
+
+/// `FT_ColorLine` of FreeType 2.11 and 2.12
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_ColorLine_2_12 {
+    extend: u32,
+    color_stop_iterator: FT_ColorStopIterator_2_12,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_Affine23 {
+    xx: FT_Fixed,
+    xy: FT_Fixed,
+    dx: FT_Fixed,
+    yx: FT_Fixed,
+    yy: FT_Fixed,
+    dy: FT_Fixed,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_OpaquePaint {
+    p: *mut u8,
+    insert_root_transform: u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_PaintColrLayers {
+    layer_iterator: FT_LayerIterator,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_PaintSolid {
+    color: FT_ColorIndex,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_PaintLinearGradient<L = FT_ColorLine> {
+    colorline: L,
+    p0: FT_Vector,
+    p1: FT_Vector,
+    p2: FT_Vector,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_PaintRadialGradient<L = FT_ColorLine> {
+    colorline: L,
+    c0: FT_Vector,
+    r0: FT_Pos,
+    c1: FT_Vector,
+    r1: FT_Pos,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_PaintSweepGradient<L = FT_ColorLine> {
+    colorline: L,
+    center: FT_Vector,
+    start_angle: FT_Fixed,
+    end_angle: FT_Fixed,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_PaintGlyph {
+    paint: FT_OpaquePaint,
+    glyph_id: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_PaintColrGlyph {
+    glyph_id: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_PaintTransform {
+    paint: FT_OpaquePaint,
+    affine: FT_Affine23,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_PaintTranslate {
+    paint: FT_OpaquePaint,
+    dx: FT_Fixed,
+    dy: FT_Fixed,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_PaintScale {
+    paint: FT_OpaquePaint,
+    scale_x: FT_Fixed,
+    scale_y: FT_Fixed,
+    center_x: FT_Fixed,
+    center_y: FT_Fixed,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_PaintRotate {
+    paint: FT_OpaquePaint,
+    angle: FT_Fixed,
+    center_x: FT_Fixed,
+    center_y: FT_Fixed,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_PaintSkew {
+    paint: FT_OpaquePaint,
+    x_skew_angle: FT_Fixed,
+    y_skew_angle: FT_Fixed,
+    center_x: FT_Fixed,
+    center_y: FT_Fixed,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct FT_PaintComposite {
+    source_paint: FT_OpaquePaint,
+    composite_mode: u32,
+    backdrop_paint: FT_OpaquePaint,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+union FT_COLR_Paint_Union {
+    colr_layers: FT_PaintColrLayers,
+    glyph: FT_PaintGlyph,
+    solid: FT_PaintSolid,
+    linear_gradient: FT_PaintLinearGradient,
+    radial_gradient: FT_PaintRadialGradient,
+    sweep_gradient: FT_PaintSweepGradient,
+    transform: FT_PaintTransform,
+    translate: FT_PaintTranslate,
+    scale: FT_PaintScale,
+    rotate: FT_PaintRotate,
+    skew: FT_PaintSkew,
+    composite: FT_PaintComposite,
+    colr_glyph: FT_PaintColrGlyph,
+}
+
+#[repr(C)]
+struct FT_COLR_Paint {
+    format: u32,
+    u: FT_COLR_Paint_Union,
+}
+
+#[repr(C)]
+struct FT_ClipBox {
+    bottom_left: FT_Vector,
+    top_left: FT_Vector,
+    top_right: FT_Vector,
+    bottom_right: FT_Vector,
+}
+
+const FT_COLOR_NO_ROOT_TRANSFORM: u32 = 1;
+
+const PAINT_COLR_LAYERS: u32 = 1;
+const PAINT_SOLID: u32 = 2;
+const PAINT_LINEAR_GRADIENT: u32 = 4;
+const PAINT_RADIAL_GRADIENT: u32 = 6;
+const PAINT_SWEEP_GRADIENT: u32 = 8;
+const PAINT_GLYPH: u32 = 10;
+const PAINT_COLR_GLYPH: u32 = 11;
+const PAINT_TRANSFORM: u32 = 12;
+const PAINT_TRANSLATE: u32 = 14;
+const PAINT_SCALE: u32 = 16;
+const PAINT_ROTATE: u32 = 24;
+const PAINT_SKEW: u32 = 28;
+const PAINT_COMPOSITE: u32 = 32;
+
+const EXTEND_REPEAT: u32 = 1;
+const EXTEND_REFLECT: u32 = 2;
+
+const COMPOSITE_CLEAR: u32 = 0;
+const COMPOSITE_SRC: u32 = 1;
+const COMPOSITE_DEST: u32 = 2;
+const COMPOSITE_SRC_OVER: u32 = 3;
+const COMPOSITE_DEST_OVER: u32 = 4;
+const COMPOSITE_SRC_IN: u32 = 5;
+const COMPOSITE_DEST_IN: u32 = 6;
+const COMPOSITE_SRC_OUT: u32 = 7;
+const COMPOSITE_DEST_OUT: u32 = 8;
+const COMPOSITE_SRC_ATOP: u32 = 9;
+const COMPOSITE_DEST_ATOP: u32 = 10;
+const COMPOSITE_XOR: u32 = 11;
+const COMPOSITE_PLUS: u32 = 12;
+const COMPOSITE_SCREEN: u32 = 13;
+const COMPOSITE_OVERLAY: u32 = 14;
+const COMPOSITE_DARKEN: u32 = 15;
+const COMPOSITE_LIGHTEN: u32 = 16;
+const COMPOSITE_COLOR_DODGE: u32 = 17;
+const COMPOSITE_COLOR_BURN: u32 = 18;
+const COMPOSITE_HARD_LIGHT: u32 = 19;
+const COMPOSITE_SOFT_LIGHT: u32 = 20;
+const COMPOSITE_DIFFERENCE: u32 = 21;
+const COMPOSITE_EXCLUSION: u32 = 22;
+const COMPOSITE_MULTIPLY: u32 = 23;
+const COMPOSITE_HSL_HUE: u32 = 24;
+const COMPOSITE_HSL_SATURATION: u32 = 25;
+const COMPOSITE_HSL_COLOR: u32 = 26;
+const COMPOSITE_HSL_LUMINOSITY: u32 = 27;
+
+/// Palette index standing for the text color
+const FOREGROUND_INDEX: u16 = 0xffff;
+
+/// Paints nested deeper than this are skipped, as they must be cyclic
+const MAX_DEPTH: u32 = 64;
+
+extern "C" {
+    fn FT_Palette_Data_Get(face: FT_Face, apalette: *mut FT_Palette_Data) -> FT_Error;
+    fn FT_Palette_Select(
+        face: FT_Face,
+        palette_index: u16,
+        apalette: *mut *mut FT_Color,
+    ) -> FT_Error;
+    fn FT_Get_Color_Glyph_Layer(
+        face: FT_Face,
+        base_glyph: u32,
+        aglyph_index: *mut u32,
+        acolor_index: *mut u32,
+        iterator: *mut FT_LayerIterator,
+    ) -> u8;
+}
+
+/// Functions of `COLR` version 1 API, which are looked up in the loaded FreeType
+///
+/// They are missing before FreeType 2.11.1, where the crate still paints version 0 glyphs
+/// and outlines instead of failing to load.
+struct ColrV1 {
+    get_color_glyph_paint: unsafe extern "C" fn(FT_Face, u32, u32, *mut FT_OpaquePaint) -> u8,
+    get_color_glyph_clip_box: unsafe extern "C" fn(FT_Face, u32, *mut FT_ClipBox) -> u8,
+    get_paint_layers:
+        unsafe extern "C" fn(FT_Face, *mut FT_LayerIterator, *mut FT_OpaquePaint) -> u8,
+    /// Takes a color stop and its iterator in the layout of the loaded version
+    get_colorline_stops: unsafe extern "C" fn(FT_Face, *mut c_void, *mut c_void) -> u8,
+    get_paint: unsafe extern "C" fn(FT_Face, FT_OpaquePaint, *mut FT_COLR_Paint) -> u8,
+    /// Whether color lines have the layout of FreeType 2.11.1 to 2.12
+    color_line_2_12: bool,
+}
+
+impl ColrV1 {
+    /// Functions of the loaded FreeType, or `None` if it lacks them
+    ///
+    /// FreeType 2.11.0 has functions of the same names taking an experimental layout,
+    /// so version 1 glyphs are painted only from 2.11.1 on.
+    fn get() -> Option<&'static ColrV1> {
+        static COLR_V1: OnceLock<Option<ColrV1>> = OnceLock::new();
+        COLR_V1
+            .get_or_init(|| {
+                let version = library_version();
+                if version < (2, 11, 1) {
+                    None
+                } else {
+                    unsafe { ColrV1::load(version < (2, 13, 0)) }
+                }
+            })
+            .as_ref()
+    }
+
+    #[cfg(all(unix, not(freetype_static)))]
+    unsafe fn load(color_line_2_12: bool) -> Option<ColrV1> {
+        /// Address of `name` in the loaded libraries as function of type `F`
+        unsafe fn symbol<F: Copy>(name: &std::ffi::CStr) -> Option<F> {
+            let address = libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr());
+            (!address.is_null()).then(|| std::mem::transmute_copy(&address))
+        }
+
+        Some(ColrV1 {
+            get_color_glyph_paint: symbol(c"FT_Get_Color_Glyph_Paint")?,
+            get_color_glyph_clip_box: symbol(c"FT_Get_Color_Glyph_ClipBox")?,
+            get_paint_layers: symbol(c"FT_Get_Paint_Layers")?,
+            get_colorline_stops: symbol(c"FT_Get_Colorline_Stops")?,
+            get_paint: symbol(c"FT_Get_Paint")?,
+            color_line_2_12,
+        })
+    }
+
+    // Functions of a static FreeType are not visible to `dlsym`, but the bundled one has them
+    #[cfg(any(freetype_static, not(unix)))]
+    unsafe fn load(color_line_2_12: bool) -> Option<ColrV1> {
+        extern "C" {
+            fn FT_Get_Color_Glyph_Paint(
+                face: FT_Face,
+                base_glyph: u32,
+                root_transform: u32,
+                paint: *mut FT_OpaquePaint,
+            ) -> u8;
+            fn FT_Get_Color_Glyph_ClipBox(
+                face: FT_Face,
+                base_glyph: u32,
+                clip_box: *mut FT_ClipBox,
+            ) -> u8;
+            fn FT_Get_Paint_Layers(
+                face: FT_Face,
+                iterator: *mut FT_LayerIterator,
+                paint: *mut FT_OpaquePaint,
+            ) -> u8;
+            fn FT_Get_Colorline_Stops(
+                face: FT_Face,
+                color_stop: *mut c_void,
+                iterator: *mut c_void,
+            ) -> u8;
+            fn FT_Get_Paint(
+                face: FT_Face,
+                opaque_paint: FT_OpaquePaint,
+                paint: *mut FT_COLR_Paint,
+            ) -> u8;
+        }
+
+        Some(ColrV1 {
+            get_color_glyph_paint: FT_Get_Color_Glyph_Paint,
+            get_color_glyph_clip_box: FT_Get_Color_Glyph_ClipBox,
+            get_paint_layers: FT_Get_Paint_Layers,
+            get_colorline_stops: FT_Get_Colorline_Stops,
+            get_paint: FT_Get_Paint,
+            color_line_2_12,
+        })
+    }
+}
+
+/// Painted color glyph
+pub(super) struct ColorGlyph {
+    /// Left edge relative to the origin in pixels
+    pub(super) left: i64,
+    /// Top edge relative to the origin in pixels, y going down
+    pub(super) top: i64,
+    pub(super) width: usize,
+    /// Premultiplied red, green, blue and alpha of each pixel
+    pub(super) pixels: Vec<[f32; 4]>,
+}
+
+/// Affine transform of 2D points
+#[derive(Clone, Copy)]
+struct Affine {
+    xx: f64,
+    xy: f64,
+    dx: f64,
+    yx: f64,
+    yy: f64,
+    dy: f64,
+}
+
+impl Affine {
+    const fn new(xx: f64, xy: f64, dx: f64, yx: f64, yy: f64, dy: f64) -> Affine {
+        Affine {
+            xx,
+            xy,
+            dx,
+            yx,
+            yy,
+            dy,
+        }
+    }
+
+    fn translate(dx: f64, dy: f64) -> Affine {
+        Affine::new(1.0, 0.0, dx, 0.0, 1.0, dy)
+    }
+
+    /// Transform which applies `other` first and then `self`
+    fn then(self, other: Affine) -> Affine {
+        Affine::new(
+            self.xx * other.xx + self.xy * other.yx,
+            self.xx * other.xy + self.xy * other.yy,
+            self.xx * other.dx + self.xy * other.dy + self.dx,
+            self.yx * other.xx + self.yy * other.yx,
+            self.yx * other.xy + self.yy * other.yy,
+            self.yx * other.dx + self.yy * other.dy + self.dy,
+        )
+    }
+
+    /// Transform applied around `(center_x, center_y)`
+    fn around(self, center_x: f64, center_y: f64) -> Affine {
+        Affine::translate(center_x, center_y)
+            .then(self)
+            .then(Affine::translate(-center_x, -center_y))
+    }
+
+    fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            self.xx * x + self.xy * y + self.dx,
+            self.yx * x + self.yy * y + self.dy,
+        )
+    }
+
+    fn invert(&self) -> Option<Affine> {
+        let determinant = self.xx * self.yy - self.xy * self.yx;
+        if determinant.abs() < f64::EPSILON {
+            return None;
+        }
+
+        let (xx, xy) = (self.yy / determinant, -self.xy / determinant);
+        let (yx, yy) = (-self.yx / determinant, self.xx / determinant);
+        Some(Affine::new(
+            xx,
+            xy,
+            -(xx * self.dx + xy * self.dy),
+            yx,
+            yy,
+            -(yx * self.dx + yy * self.dy),
+        ))
+    }
+}
+
+/// Bounding box of points, as `(x_min, y_min, x_max, y_max)`
+#[derive(Clone, Copy)]
+struct Bounds(f64, f64, f64, f64);
+
+impl Bounds {
+    fn of(points: impl IntoIterator<Item = (f64, f64)>) -> Option<Bounds> {
+        points.into_iter().fold(None, |bounds, (x, y)| {
+            Some(match bounds {
+                Some(Bounds(x_min, y_min, x_max, y_max)) => {
+                    Bounds(x_min.min(x), y_min.min(y), x_max.max(x), y_max.max(y))
+                }
+                None => Bounds(x, y, x, y),
+            })
+        })
+    }
+
+    fn union(bounds: Option<Bounds>, other: Option<Bounds>) -> Option<Bounds> {
+        match (bounds, other) {
+            (Some(Bounds(a, b, c, d)), Some(Bounds(e, f, g, h))) => {
+                Some(Bounds(a.min(e), b.min(f), c.max(g), d.max(h)))
+            }
+            _ => bounds.or(other),
+        }
+    }
+
+    fn corners(&self) -> [(f64, f64); 4] {
+        let Bounds(x_min, y_min, x_max, y_max) = *self;
+
+        [
+            (x_min, y_min),
+            (x_min, y_max),
+            (x_max, y_min),
+            (x_max, y_max),
+        ]
+    }
+
+    fn transform(&self, matrix: &Affine) -> Option<Bounds> {
+        Bounds::of(self.corners().map(|(x, y)| matrix.apply(x, y)))
+    }
+}
+
+/// Color glyph of either version of `COLR` table
+#[derive(Clone, Copy)]
+enum Root {
+    Layers(u32),
+    Paint(FT_OpaquePaint),
+}
+
+/// Paints color glyphs of a sized face
+#[derive(Clone)]
+pub(super) struct Painter {
+    face: FT_Face,
+    library: FT_Library,
+    /// `None` unless FreeType paints version 1 glyphs
+    v1: Option<&'static ColrV1>,
+    /// Premultiplied colors of the selected palette
+    palette: Vec<[f32; 4]>,
+    foreground: [f32; 4],
+}
+
+impl Painter {
+    /// Creates painter with `palette_index`th palette, or the first one if it is out of range
+    ///
+    /// `foreground` is used for the colors standing for the text color.
+    pub(super) fn new(
+        face: FT_Face,
+        library: FT_Library,
+        palette_index: u16,
+        foreground: Color,
+    ) -> Painter {
+        let palette = unsafe {
+            let mut data = std::mem::zeroed::<FT_Palette_Data>();
+            let mut entries = std::ptr::null_mut();
+            if FT_Palette_Data_Get(face, &mut data) != 0 || data.num_palettes == 0 {
+                Vec::new()
+            } else {
+                let index = if palette_index < data.num_palettes {
+                    palette_index
+                } else {
+                    0
+                };
+                if FT_Palette_Select(face, index, &mut entries) != 0 || entries.is_null() {
+                    Vec::new()
+                } else {
+                    std::slice::from_raw_parts(entries, data.num_palette_entries as usize)
+                        .iter()
+                        .map(|color| premultiply([color.red, color.green, color.blue, color.alpha]))
+                        .collect()
+                }
+            }
+        };
+
+        Painter {
+            face,
+            library,
+            v1: ColrV1::get(),
+            palette,
+            foreground: premultiply([foreground.r, foreground.g, foreground.b, foreground.a]),
+        }
+    }
+
+    fn root(&self, glyph_index: u32) -> Option<Root> {
+        unsafe {
+            if let Some(v1) = self.v1 {
+                let mut paint = std::mem::zeroed::<FT_OpaquePaint>();
+                if (v1.get_color_glyph_paint)(
+                    self.face,
+                    glyph_index,
+                    FT_COLOR_NO_ROOT_TRANSFORM,
+                    &mut paint,
+                ) != 0
+                {
+                    return Some(Root::Paint(paint));
+                }
+            }
+
+            let (mut layer_glyph, mut color_index) = (0, 0);
+            let mut iterator = std::mem::zeroed::<FT_LayerIterator>();
+            if FT_Get_Color_Glyph_Layer(
+                self.face,
+                glyph_index,
+                &mut layer_glyph,
+                &mut color_index,
+                &mut iterator,
+            ) != 0
+            {
+                return Some(Root::Layers(glyph_index));
+            }
+        }
+
+        None
+    }
+
+    /// Layers of version 0 glyph as glyph index and palette index
+    fn layers(&self, glyph_index: u32) -> Vec<(u32, u16)> {
+        let mut layers = Vec::new();
+        unsafe {
+            let (mut layer_glyph, mut color_index) = (0, 0);
+            let mut iterator = std::mem::zeroed::<FT_LayerIterator>();
+            while FT_Get_Color_Glyph_Layer(
+                self.face,
+                glyph_index,
+                &mut layer_glyph,
+                &mut color_index,
+                &mut iterator,
+            ) != 0
+            {
+                layers.push((layer_glyph, color_index as u16));
+            }
+        }
+
+        layers
+    }
+
+    /// Transform from font units to 26.6 pixels divided by 64, y going up
+    fn scale(&self) -> Affine {
+        let metrics = unsafe { (*(*self.face).size).metrics };
+
+        Affine::new(
+            metrics.x_scale as f64 / 65536.0 / 64.0,
+            0.0,
+            0.0,
+            0.0,
+            metrics.y_scale as f64 / 65536.0 / 64.0,
+            0.0,
+        )
+    }
+
+    /// Bounds of color glyph in pixels relative to the origin, y going up,
+    /// or `None` unless glyph is a color glyph
+    pub(super) fn bounds(&self, glyph_index: u32) -> Result<Option<(f64, f64, f64, f64)>> {
+        let Some(root) = self.root(glyph_index) else {
+            return Ok(None);
+        };
+        let bounds = self
+            .root_bounds(glyph_index, root)?
+            .map(|Bounds(x_min, y_min, x_max, y_max)| (x_min, y_min, x_max, y_max));
+
+        Ok(Some(bounds.unwrap_or_default()))
+    }
+
+    fn root_bounds(&self, glyph_index: u32, root: Root) -> Result<Option<Bounds>> {
+        let scale = self.scale();
+        match root {
+            Root::Layers(glyph_index) => {
+                let mut bounds = None;
+                for (layer_glyph, _) in self.layers(glyph_index) {
+                    bounds = Bounds::union(bounds, self.glyph_bounds(layer_glyph, &scale)?);
+                }
+
+                Ok(bounds)
+            }
+            Root::Paint(paint) => {
+                let mut clip_box = unsafe { std::mem::zeroed::<FT_ClipBox>() };
+                if self.v1.is_some_and(|v1| unsafe {
+                    (v1.get_color_glyph_clip_box)(self.face, glyph_index, &mut clip_box) != 0
+                }) {
+                    let corners = [
+                        clip_box.bottom_left,
+                        clip_box.top_left,
+                        clip_box.top_right,
+                        clip_box.bottom_right,
+                    ];
+                    return Ok(Bounds::of(
+                        corners.map(|corner| (corner.x as f64 / 64.0, corner.y as f64 / 64.0)),
+                    ));
+                }
+
+                self.paint_bounds(paint, scale, 0)
+            }
+        }
+    }
+
+    /// Bounds of outline of glyph transformed by `matrix`
+    fn glyph_bounds(&self, glyph_index: u32, matrix: &Affine) -> Result<Option<Bounds>> {
+        self.load_unscaled(glyph_index)?;
+        let mut cbox = FT_BBox {
+            xMin: 0,
+            yMin: 0,
+            xMax: 0,
+            yMax: 0,
+        };
+        unsafe {
+            let glyph = (*self.face).glyph;
+            if (*glyph).format != FT_Glyph_Format::FT_GLYPH_FORMAT_OUTLINE
+                || (*glyph).outline.n_points == 0
+            {
+                return Ok(None);
+            }
+            FT_Outline_Get_CBox(&(*glyph).outline, &mut cbox);
+        }
+
+        Ok(Bounds(
+            cbox.xMin as f64,
+            cbox.yMin as f64,
+            cbox.xMax as f64,
+            cbox.yMax as f64,
+        )
+        .transform(matrix))
+    }
+
+    /// Bounds of what version 1 paint draws, where only glyphs bound paints
+    fn paint_bounds(
+        &self,
+        opaque_paint: FT_OpaquePaint,
+        matrix: Affine,
+        depth: u32,
+    ) -> Result<Option<Bounds>> {
+        let Some(paint) = self.get_paint(opaque_paint, depth) else {
+            return Ok(None);
+        };
+
+        unsafe {
+            match paint.format {
+                PAINT_COLR_LAYERS => {
+                    let mut iterator = paint.u.colr_layers.layer_iterator;
+                    let mut layer = std::mem::zeroed::<FT_OpaquePaint>();
+                    let mut bounds = None;
+                    while self.next_layer(&mut iterator, &mut layer) {
+                        bounds =
+                            Bounds::union(bounds, self.paint_bounds(layer, matrix, depth + 1)?);
+                    }
+
+                    Ok(bounds)
+                }
+                PAINT_GLYPH => self.glyph_bounds(paint.u.glyph.glyph_id, &matrix),
+                PAINT_COLR_GLYPH => match self.root(paint.u.colr_glyph.glyph_id) {
+                    Some(Root::Paint(root)) => self.paint_bounds(root, matrix, depth + 1),
+                    _ => Ok(None),
+                },
+                PAINT_COMPOSITE => {
+                    let composite = paint.u.composite;
+                    Ok(Bounds::union(
+                        self.paint_bounds(composite.source_paint, matrix, depth + 1)?,
+                        self.paint_bounds(composite.backdrop_paint, matrix, depth + 1)?,
+                    ))
+                }
+                _ => match transform_of(&paint) {
+                    Some((child, transform)) => {
+                        self.paint_bounds(child, matrix.then(transform), depth + 1)
+                    }
+                    // Fills are bounded by glyphs they are in
+                    None => Ok(None),
+                },
+            }
+        }
+    }
+
+    fn get_paint(&self, opaque_paint: FT_OpaquePaint, depth: u32) -> Option<FT_COLR_Paint> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+
+        let v1 = self.v1?;
+        unsafe {
+            let mut paint = std::mem::zeroed::<FT_COLR_Paint>();
+            if (v1.get_paint)(self.face, opaque_paint, &mut paint) != 0 {
+                Some(paint)
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Reads the next paint of a `PaintColrLayers` into `layer`, or returns false at the end
+    unsafe fn next_layer(
+        &self,
+        iterator: &mut FT_LayerIterator,
+        layer: &mut FT_OpaquePaint,
+    ) -> bool {
+        self.v1
+            .is_some_and(|v1| (v1.get_paint_layers)(self.face, iterator, layer) != 0)
+    }
+
+    /// Reads the next stop of a color line into `stop`, or returns false at the end
+    ///
+    /// `stop` and `iterator` must have the layout of the loaded version.
+    unsafe fn next_stop<S, I>(&self, iterator: &mut I, stop: &mut S) -> bool {
+        self.v1.is_some_and(|v1| {
+            (v1.get_colorline_stops)(
+                self.face,
+                stop as *mut S as *mut c_void,
+                iterator as *mut I as *mut c_void,
+            ) != 0
+        })
+    }
+
+    /// Paints color glyph whose origin is at fraction (`offset_x`, `offset_y`) of a pixel,
+    /// or returns `None` unless glyph is a color glyph
+    ///
+    /// `rotated` turns the glyph 90 degrees clockwise, for sideways glyphs in vertical text.
+    pub(super) fn paint(
+        &self,
+        glyph_index: u32,
+        offset_x: f64,
+        offset_y: f64,
+        rotated: bool,
+    ) -> Result<Option<ColorGlyph>> {
+        let Some(root) = self.root(glyph_index) else {
+            return Ok(None);
+        };
+        // From pixels relative to the origin with y going up, to pixels with y going down
+        let place = if rotated {
+            Affine::new(0.0, 1.0, offset_x, 1.0, 0.0, offset_y)
+        } else {
+            Affine::new(1.0, 0.0, offset_x, 0.0, -1.0, offset_y)
+        };
+        let Some(bounds) = self
+            .root_bounds(glyph_index, root)?
+            .and_then(|bounds| bounds.transform(&place))
+        else {
+            return Ok(Some(ColorGlyph {
+                left: 0,
+                top: 0,
+                width: 0,
+                pixels: Vec::new(),
+            }));
+        };
+
+        let Bounds(x_min, y_min, x_max, y_max) = bounds;
+        let (left, top) = (x_min.floor(), y_min.floor());
+        let width = (x_max.ceil() - left) as usize;
+        let rows = (y_max.ceil() - top) as usize;
+        let matrix = Affine::translate(-left, -top)
+            .then(place)
+            .then(self.scale());
+
+        let mut canvas = Canvas {
+            width,
+            rows,
+            pixels: vec![[0.0; 4]; width * rows],
+        };
+        let clip = vec![1.0; width * rows];
+        match root {
+            Root::Layers(glyph_index) => {
+                for (layer_glyph, palette_index) in self.layers(glyph_index) {
+                    let mask = self.glyph_mask(layer_glyph, &matrix, &clip, width, rows)?;
+                    canvas.fill(&mask, |_, _| self.color(palette_index, 1.0));
+                }
+            }
+            Root::Paint(paint) => self.paint_into(paint, matrix, &clip, &mut canvas, 0)?,
+        }
+
+        Ok(Some(ColorGlyph {
+            left: left as i64,
+            top: top as i64,
+            width,
+            pixels: canvas.pixels,
+        }))
+    }
+
+    /// Paints version 1 paint onto `canvas`, inside `clip` which is coverage of each pixel
+    fn paint_into(
+        &self,
+        opaque_paint: FT_OpaquePaint,
+        matrix: Affine,
+        clip: &[f32],
+        canvas: &mut Canvas,
+        depth: u32,
+    ) -> Result<()> {
+        let Some(paint) = self.get_paint(opaque_paint, depth) else {
+            return Ok(());
+        };
+
+        unsafe {
+            match paint.format {
+                PAINT_COLR_LAYERS => {
+                    let mut iterator = paint.u.colr_layers.layer_iterator;
+                    let mut layer = std::mem::zeroed::<FT_OpaquePaint>();
+                    while self.next_layer(&mut iterator, &mut layer) {
+                        self.paint_into(layer, matrix, clip, canvas, depth + 1)?;
+                    }
+                }
+                PAINT_SOLID => {
+                    let color = paint.u.solid.color;
+                    canvas.fill(clip, |_, _| {
+                        self.color(color.palette_index, f2dot14(color.alpha))
+                    });
+                }
+                PAINT_LINEAR_GRADIENT | PAINT_RADIAL_GRADIENT | PAINT_SWEEP_GRADIENT => {
+                    // Gradients are given in font units
+                    if let Some(inverse) = matrix.invert() {
+                        let gradient = Gradient::new(self, &paint);
+                        canvas.fill(clip, |x, y| {
+                            let (x, y) = inverse.apply(x, y);
+                            gradient.color_at(x, y)
+                        });
+                    }
+                }
+                PAINT_GLYPH => {
+                    let glyph = paint.u.glyph;
+                    let mask =
+                        self.glyph_mask(glyph.glyph_id, &matrix, clip, canvas.width, canvas.rows)?;
+                    self.paint_into(glyph.paint, matrix, &mask, canvas, depth + 1)?;
+                }
+                PAINT_COLR_GLYPH => {
+                    if let Some(Root::Paint(root)) = self.root(paint.u.colr_glyph.glyph_id) {
+                        self.paint_into(root, matrix, clip, canvas, depth + 1)?;
+                    }
+                }
+                PAINT_COMPOSITE => {
+                    let composite = paint.u.composite;
+                    let mut backdrop = canvas.empty();
+                    self.paint_into(
+                        composite.backdrop_paint,
+                        matrix,
+                        clip,
+                        &mut backdrop,
+                        depth + 1,
+                    )?;
+                    let mut source = canvas.empty();
+                    self.paint_into(composite.source_paint, matrix, clip, &mut source, depth + 1)?;
+
+                    for ((pixel, source), backdrop) in canvas
+                        .pixels
+                        .iter_mut()
+                        .zip(source.pixels)
+                        .zip(backdrop.pixels)
+                    {
+                        let composited =
+                            composite_colors(composite.composite_mode, source, backdrop);
+                        *pixel = over(composited, *pixel);
+                    }
+                }
+                _ => {
+                    if let Some((child, transform)) = transform_of(&paint) {
+                        self.paint_into(child, matrix.then(transform), clip, canvas, depth + 1)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Premultiplied color of palette entry, with its alpha multiplied by `alpha`
+    fn color(&self, palette_index: u16, alpha: f32) -> [f32; 4] {
+        let color = if palette_index == FOREGROUND_INDEX {
+            self.foreground
+        } else {
+            // Colors out of the palette are invisible
+            self.palette
+                .get(palette_index as usize)
+                .copied()
+                .unwrap_or_default()
+        };
+
+        color.map(|channel| channel * alpha.clamp(0.0, 1.0))
+    }
+
+    /// Loads outline of glyph in font units
+    fn load_unscaled(&self, glyph_index: u32) -> Result<()> {
+        let err = unsafe {
+            FT_Load_Glyph(
+                self.face,
+                glyph_index,
+                (FT_LOAD_NO_SCALE | FT_LOAD_NO_BITMAP) as i32,
+            )
+        };
+
+        error_if_not_zero!(err)
+    }
+
+    /// Coverage of glyph transformed by `matrix` within `clip`
+    fn glyph_mask(
+        &self,
+        glyph_index: u32,
+        matrix: &Affine,
+        clip: &[f32],
+        width: usize,
+        rows: usize,
+    ) -> Result<Vec<f32>> {
+        self.load_unscaled(glyph_index)?;
+        let mut buffer = vec![0u8; width * rows];
+        unsafe {
+            let glyph = (*self.face).glyph;
+            if (*glyph).format != FT_Glyph_Format::FT_GLYPH_FORMAT_OUTLINE {
+                return Ok(vec![0.0; width * rows]);
+            }
+
+            // Outline is rendered with y going up from the bottom edge in 26.6 pixels
+            let outline = &mut (*glyph).outline;
+            let points = std::slice::from_raw_parts_mut(outline.points, outline.n_points as usize);
+            for point in points {
+                let (x, y) = matrix.apply(point.x as f64, point.y as f64);
+                point.x = (x * 64.0).round() as FT_Pos;
+                point.y = ((rows as f64 - y) * 64.0).round() as FT_Pos;
+            }
+
+            let bitmap = FT_Bitmap {
+                rows: rows as u32,
+                width: width as u32,
+                pitch: width as i32,
+                buffer: buffer.as_mut_ptr(),
+                num_grays: 256,
+                pixel_mode: FT_Pixel_Mode_::FT_PIXEL_MODE_GRAY as u8,
+                palette_mode: 0,
+                palette: std::ptr::null_mut(),
+            };
+            let err = FT_Outline_Get_Bitmap(self.library, outline, &bitmap);
+            error_if_not_zero!(err)?;
+        }
+
+        // Bitmap rows go from the top
+        Ok(buffer
+            .iter()
+            .zip(clip)
+            .map(|(coverage, clip)| *coverage as f32 / 255.0 * clip)
+            .collect())
+    }
+}
+
+/// Child paint and transform of transform paints
+fn transform_of(paint: &FT_COLR_Paint) -> Option<(FT_OpaquePaint, Affine)> {
+    unsafe {
+        match paint.format {
+            PAINT_TRANSFORM => {
+                let FT_PaintTransform { paint, affine } = paint.u.transform;
+                Some((
+                    paint,
+                    Affine::new(
+                        fixed(affine.xx),
+                        fixed(affine.xy),
+                        fixed(affine.dx),
+                        fixed(affine.yx),
+                        fixed(affine.yy),
+                        fixed(affine.dy),
+                    ),
+                ))
+            }
+            PAINT_TRANSLATE => {
+                let translate = paint.u.translate;
+                Some((
+                    translate.paint,
+                    Affine::translate(fixed(translate.dx), fixed(translate.dy)),
+                ))
+            }
+            PAINT_SCALE => {
+                let scale = paint.u.scale;
+                let matrix = Affine::new(
+                    fixed(scale.scale_x),
+                    0.0,
+                    0.0,
+                    0.0,
+                    fixed(scale.scale_y),
+                    0.0,
+                );
+                Some((
+                    scale.paint,
+                    matrix.around(fixed(scale.center_x), fixed(scale.center_y)),
+                ))
+            }
+            PAINT_ROTATE => {
+                // Angles are in half turns, counter-clockwise
+                let rotate = paint.u.rotate;
+                let (sin, cos) = (fixed(rotate.angle) * std::f64::consts::PI).sin_cos();
+                let matrix = Affine::new(cos, -sin, 0.0, sin, cos, 0.0);
+                Some((
+                    rotate.paint,
+                    matrix.around(fixed(rotate.center_x), fixed(rotate.center_y)),
+                ))
+            }
+            PAINT_SKEW => {
+                // Positive angles turn axes counter-clockwise
+                let skew = paint.u.skew;
+                let x_skew = (fixed(skew.x_skew_angle) * std::f64::consts::PI).tan();
+                let y_skew = (fixed(skew.y_skew_angle) * std::f64::consts::PI).tan();
+                let matrix = Affine::new(1.0, -x_skew, 0.0, y_skew, 1.0, 0.0);
+                Some((
+                    skew.paint,
+                    matrix.around(fixed(skew.center_x), fixed(skew.center_y)),
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Layer which paints are composited into
+struct Canvas {
+    width: usize,
+    rows: usize,
+    /// Premultiplied red, green, blue and alpha of each pixel
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Canvas {
+    fn empty(&self) -> Canvas {
+        Canvas {
+            width: self.width,
+            rows: self.rows,
+            pixels: vec![[0.0; 4]; self.pixels.len()],
+        }
+    }
+
+    /// Fills pixels covered by `coverage` with color at center of each pixel
+    fn fill(&mut self, coverage: &[f32], color_at: impl Fn(f64, f64) -> [f32; 4]) {
+        for (index, (pixel, coverage)) in self.pixels.iter_mut().zip(coverage).enumerate() {
+            if *coverage == 0.0 {
+                continue;
+            }
+
+            let x = (index % self.width) as f64 + 0.5;
+            let y = (index / self.width) as f64 + 0.5;
+            let color = color_at(x, y).map(|channel| channel * coverage);
+            *pixel = over(color, *pixel);
+        }
+    }
+}
+
+/// Gradient paint with its color line
+struct Gradient {
+    kind: GradientKind,
+    extend: u32,
+    /// Offsets and premultiplied colors of stops in order
+    stops: Vec<(f64, [f32; 4])>,
+}
+
+/// Color line in layout of some FreeType version
+trait ColorLine: Copy {
+    fn extend(&self) -> u32;
+
+    /// Offsets and premultiplied colors of stops in order of the font
+    unsafe fn stops(&self, painter: &Painter) -> Vec<(f64, [f32; 4])>;
+}
+
+impl ColorLine for FT_ColorLine {
+    fn extend(&self) -> u32 {
+        self.extend
+    }
+
+    unsafe fn stops(&self, painter: &Painter) -> Vec<(f64, [f32; 4])> {
+        let mut stops = Vec::new();
+        let mut iterator = self.color_stop_iterator;
+        let mut stop = std::mem::zeroed::<FT_ColorStop>();
+        while painter.next_stop(&mut iterator, &mut stop) {
+            let color = painter.color(stop.color.palette_index, f2dot14(stop.color.alpha));
+            stops.push((fixed(stop.stop_offset), color));
+        }
+        stops
+    }
+}
+
+impl ColorLine for FT_ColorLine_2_12 {
+    fn extend(&self) -> u32 {
+        self.extend
+    }
+
+    unsafe fn stops(&self, painter: &Painter) -> Vec<(f64, [f32; 4])> {
+        let mut stops = Vec::new();
+        let mut iterator = self.color_stop_iterator;
+        let mut stop = std::mem::zeroed::<FT_ColorStop_2_12>();
+        while painter.next_stop(&mut iterator, &mut stop) {
+            let color = painter.color(stop.color.palette_index, f2dot14(stop.color.alpha));
+            stops.push((f2dot14(stop.stop_offset) as f64, color));
+        }
+        stops
+    }
+}
+
+enum GradientKind {
+    /// Points are projected onto the line from the start to the end
+    Linear { start: (f64, f64), end: (f64, f64) },
+    /// Circles are interpolated from the start circle to the end circle
+    Radial {
+        start: (f64, f64),
+        start_radius: f64,
+        end: (f64, f64),
+        end_radius: f64,
+    },
+    /// Angles in degrees, counter-clockwise from the positive x axis
+    Sweep {
+        center: (f64, f64),
+        start_angle: f64,
+        end_angle: f64,
+    },
+}
+
+impl Gradient {
+    /// Reads gradient of `paint`, whose format must be one of gradients
+    unsafe fn new(painter: &Painter, paint: &FT_COLR_Paint) -> Gradient {
+        if painter.v1.is_some_and(|v1| v1.color_line_2_12) {
+            Gradient::read::<FT_ColorLine_2_12>(painter, paint)
+        } else {
+            Gradient::read::<FT_ColorLine>(painter, paint)
+        }
+    }
+
+    /// Reads gradient of `paint` with color line of layout `L`
+    unsafe fn read<L: ColorLine>(painter: &Painter, paint: &FT_COLR_Paint) -> Gradient {
+        let vector = |vector: FT_Vector| (fixed(vector.x), fixed(vector.y));
+        // Gradients of every layout start the union
+        let u = &paint.u as *const FT_COLR_Paint_Union;
+        let (kind, color_line) = match paint.format {
+            PAINT_LINEAR_GRADIENT => {
+                let gradient = *(u as *const FT_PaintLinearGradient<L>);
+                let (p0, p1, p2) = (
+                    vector(gradient.p0),
+                    vector(gradient.p1),
+                    vector(gradient.p2),
+                );
+                // The end is p1 projected onto the line through p0, perpendicular to p0-p2
+                let normal = (p2.1 - p0.1, p0.0 - p2.0);
+                let length = normal.0 * normal.0 + normal.1 * normal.1;
+                let end = if length == 0.0 {
+                    p1
+                } else {
+                    let distance = ((p1.0 - p0.0) * normal.0 + (p1.1 - p0.1) * normal.1) / length;
+                    (p0.0 + normal.0 * distance, p0.1 + normal.1 * distance)
+                };
+
+                (GradientKind::Linear { start: p0, end }, gradient.colorline)
+            }
+            PAINT_RADIAL_GRADIENT => {
+                let gradient = *(u as *const FT_PaintRadialGradient<L>);
+                (
+                    GradientKind::Radial {
+                        start: vector(gradient.c0),
+                        start_radius: fixed(gradient.r0),
+                        end: vector(gradient.c1),
+                        end_radius: fixed(gradient.r1),
+                    },
+                    gradient.colorline,
+                )
+            }
+            _ => {
+                // OpenType 1.9.1 shifts angles by a half turn, so that full turn fits in F2DOT14
+                let gradient = *(u as *const FT_PaintSweepGradient<L>);
+                (
+                    GradientKind::Sweep {
+                        center: vector(gradient.center),
+                        start_angle: (fixed(gradient.start_angle) + 1.0) * 180.0,
+                        end_angle: (fixed(gradient.end_angle) + 1.0) * 180.0,
+                    },
+                    gradient.colorline,
+                )
+            }
+        };
+
+        let mut stops = color_line.stops(painter);
+        stops.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        Gradient {
+            kind,
+            extend: color_line.extend(),
+            stops,
+        }
+    }
+
+    /// Color at point in font units
+    fn color_at(&self, x: f64, y: f64) -> [f32; 4] {
+        let position = match self.kind {
+            GradientKind::Linear { start, end } => {
+                let direction = (end.0 - start.0, end.1 - start.1);
+                let length = direction.0 * direction.0 + direction.1 * direction.1;
+                if length == 0.0 {
+                    return [0.0; 4];
+                }
+
+                ((x - start.0) * direction.0 + (y - start.1) * direction.1) / length
+            }
+            GradientKind::Radial {
+                start,
+                start_radius,
+                end,
+                end_radius,
+            } => {
+                match radial_position(
+                    (x - start.0, y - start.1),
+                    (end.0 - start.0, end.1 - start.1),
+                    start_radius,
+                    end_radius - start_radius,
+                ) {
+                    Some(position) => position,
+                    None => return [0.0; 4],
+                }
+            }
+            GradientKind::Sweep {
+                center,
+                start_angle,
+                end_angle,
+            } => {
+                let angle = (y - center.1)
+                    .atan2(x - center.0)
+                    .to_degrees()
+                    .rem_euclid(360.0);
+                if start_angle == end_angle {
+                    if angle < start_angle {
+                        0.0
+                    } else {
+                        1.0
+                    }
+                } else {
+                    (angle - start_angle) / (end_angle - start_angle)
+                }
+            }
+        };
+
+        self.color_line_at(position)
+    }
+
+    /// Color of color line at `position`, extended beyond the first and the last stops
+    fn color_line_at(&self, position: f64) -> [f32; 4] {
+        let (Some(first), Some(last)) = (self.stops.first(), self.stops.last()) else {
+            return [0.0; 4];
+        };
+        let (start, length) = (first.0, last.0 - first.0);
+        let position = if length <= 0.0 {
+            position
+        } else {
+            let relative = (position - start) / length;
+            let relative = match self.extend {
+                EXTEND_REPEAT => relative.rem_euclid(1.0),
+                EXTEND_REFLECT => 1.0 - ((relative.rem_euclid(2.0)) - 1.0).abs(),
+                _ => relative.clamp(0.0, 1.0),
+            };
+            start + relative * length
+        };
+
+        if position <= first.0 {
+            return first.1;
+        }
+        for pair in self.stops.windows(2) {
+            let ((start, start_color), (end, end_color)) = (pair[0], pair[1]);
+            if position <= end {
+                let ratio = if end > start {
+                    ((position - start) / (end - start)) as f32
+                } else {
+                    1.0
+                };
+                return [0, 1, 2, 3]
+                    .map(|i| start_color[i] + (end_color[i] - start_color[i]) * ratio);
+            }
+        }
+
+        last.1
+    }
+}
+
+/// Largest position of circles of two-point conical gradient passing through `point`,
+/// where circles don't have negative radius
+///
+/// `point` and `center_delta` are relative to the start circle.
+fn radial_position(
+    point: (f64, f64),
+    center_delta: (f64, f64),
+    radius: f64,
+    radius_delta: f64,
+) -> Option<f64> {
+    // |point - center_delta * t| = radius + radius_delta * t
+    let a = center_delta.0 * center_delta.0 + center_delta.1 * center_delta.1
+        - radius_delta * radius_delta;
+    let b = point.0 * center_delta.0 + point.1 * center_delta.1 + radius * radius_delta;
+    let c = point.0 * point.0 + point.1 * point.1 - radius * radius;
+    let is_valid = |position: f64| radius + radius_delta * position >= 0.0;
+
+    if a.abs() < f64::EPSILON {
+        if b == 0.0 {
+            return None;
+        }
+        let position = c / (2.0 * b);
+        return is_valid(position).then_some(position);
+    }
+
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let (larger, smaller) = if a > 0.0 {
+        ((b + root) / a, (b - root) / a)
+    } else {
+        ((b - root) / a, (b + root) / a)
+    };
+
+    [larger, smaller]
+        .into_iter()
+        .find(|position| is_valid(*position))
+}
+
+/// Porter-Duff over of premultiplied colors
+fn over(source: [f32; 4], backdrop: [f32; 4]) -> [f32; 4] {
+    [0, 1, 2, 3].map(|i| source[i] + backdrop[i] * (1.0 - source[3]))
+}
+
+/// Composites premultiplied colors as in W3C Compositing and Blending
+fn composite_colors(mode: u32, source: [f32; 4], backdrop: [f32; 4]) -> [f32; 4] {
+    let (source_alpha, backdrop_alpha) = (source[3], backdrop[3]);
+    let porter_duff = |source_factor: f32, backdrop_factor: f32| {
+        [0, 1, 2, 3].map(|i| source[i] * source_factor + backdrop[i] * backdrop_factor)
+    };
+
+    match mode {
+        COMPOSITE_CLEAR => [0.0; 4],
+        COMPOSITE_SRC => source,
+        COMPOSITE_DEST => backdrop,
+        COMPOSITE_SRC_OVER => porter_duff(1.0, 1.0 - source_alpha),
+        COMPOSITE_DEST_OVER => porter_duff(1.0 - backdrop_alpha, 1.0),
+        COMPOSITE_SRC_IN => porter_duff(backdrop_alpha, 0.0),
+        COMPOSITE_DEST_IN => porter_duff(0.0, source_alpha),
+        COMPOSITE_SRC_OUT => porter_duff(1.0 - backdrop_alpha, 0.0),
+        COMPOSITE_DEST_OUT => porter_duff(0.0, 1.0 - source_alpha),
+        COMPOSITE_SRC_ATOP => porter_duff(backdrop_alpha, 1.0 - source_alpha),
+        COMPOSITE_DEST_ATOP => porter_duff(1.0 - backdrop_alpha, source_alpha),
+        COMPOSITE_XOR => porter_duff(1.0 - backdrop_alpha, 1.0 - source_alpha),
+        COMPOSITE_PLUS => [0, 1, 2, 3].map(|i| (source[i] + backdrop[i]).min(1.0)),
+        _ => blend(mode, source, backdrop),
+    }
+}
+
+/// Blends premultiplied colors with source-over, mixing colors by blend mode
+fn blend(mode: u32, source: [f32; 4], backdrop: [f32; 4]) -> [f32; 4] {
+    let unpremultiply = |color: [f32; 4]| {
+        if color[3] > 0.0 {
+            [
+                color[0] / color[3],
+                color[1] / color[3],
+                color[2] / color[3],
+            ]
+        } else {
+            [0.0; 3]
+        }
+    };
+    let (source_color, backdrop_color) = (unpremultiply(source), unpremultiply(backdrop));
+    let mixed = match mode {
+        COMPOSITE_HSL_HUE => set_luminosity(
+            set_saturation(source_color, saturation(backdrop_color)),
+            luminosity(backdrop_color),
+        ),
+        COMPOSITE_HSL_SATURATION => set_luminosity(
+            set_saturation(backdrop_color, saturation(source_color)),
+            luminosity(backdrop_color),
+        ),
+        COMPOSITE_HSL_COLOR => set_luminosity(source_color, luminosity(backdrop_color)),
+        COMPOSITE_HSL_LUMINOSITY => set_luminosity(backdrop_color, luminosity(source_color)),
+        _ => [0, 1, 2].map(|i| blend_channel(mode, source_color[i], backdrop_color[i])),
+    };
+
+    let (source_alpha, backdrop_alpha) = (source[3], backdrop[3]);
+    let [r, g, b] = [0, 1, 2].map(|i| {
+        source[i] * (1.0 - backdrop_alpha)
+            + backdrop[i] * (1.0 - source_alpha)
+            + source_alpha * backdrop_alpha * mixed[i]
+    });
+
+    [
+        r,
+        g,
+        b,
+        source_alpha + backdrop_alpha - source_alpha * backdrop_alpha,
+    ]
+}
+
+/// Separable blend modes
+fn blend_channel(mode: u32, source: f32, backdrop: f32) -> f32 {
+    let hard_light = |source: f32, backdrop: f32| {
+        if source <= 0.5 {
+            backdrop * 2.0 * source
+        } else {
+            let source = 2.0 * source - 1.0;
+            backdrop + source - backdrop * source
+        }
+    };
+
+    match mode {
+        COMPOSITE_SCREEN => backdrop + source - backdrop * source,
+        COMPOSITE_OVERLAY => hard_light(backdrop, source),
+        COMPOSITE_DARKEN => source.min(backdrop),
+        COMPOSITE_LIGHTEN => source.max(backdrop),
+        COMPOSITE_COLOR_DODGE => {
+            if backdrop == 0.0 {
+                0.0
+            } else if source >= 1.0 {
+                1.0
+            } else {
+                (backdrop / (1.0 - source)).min(1.0)
+            }
+        }
+        COMPOSITE_COLOR_BURN => {
+            if backdrop >= 1.0 {
+                1.0
+            } else if source <= 0.0 {
+                0.0
+            } else {
+                1.0 - ((1.0 - backdrop) / source).min(1.0)
+            }
+        }
+        COMPOSITE_HARD_LIGHT => hard_light(source, backdrop),
+        COMPOSITE_SOFT_LIGHT => {
+            if source <= 0.5 {
+                backdrop - (1.0 - 2.0 * source) * backdrop * (1.0 - backdrop)
+            } else {
+                let d = if backdrop <= 0.25 {
+                    ((16.0 * backdrop - 12.0) * backdrop + 4.0) * backdrop
+                } else {
+                    backdrop.sqrt()
+                };
+                backdrop + (2.0 * source - 1.0) * (d - backdrop)
+            }
+        }
+        COMPOSITE_DIFFERENCE => (source - backdrop).abs(),
+        COMPOSITE_EXCLUSION => source + backdrop - 2.0 * source * backdrop,
+        COMPOSITE_MULTIPLY => source * backdrop,
+        _ => source,
+    }
+}
+
+fn luminosity(color: [f32; 3]) -> f32 {
+    0.3 * color[0] + 0.59 * color[1] + 0.11 * color[2]
+}
+
+fn set_luminosity(color: [f32; 3], target: f32) -> [f32; 3] {
+    let delta = target - luminosity(color);
+    let color = color.map(|channel| channel + delta);
+
+    // Clips color into gamut, keeping its luminosity
+    let luminosity = luminosity(color);
+    let min = color[0].min(color[1]).min(color[2]);
+    let max = color[0].max(color[1]).max(color[2]);
+    color.map(|mut channel| {
+        if min < 0.0 {
+            channel = luminosity + (channel - luminosity) * luminosity / (luminosity - min);
+        }
+        if max > 1.0 {
+            channel = luminosity + (channel - luminosity) * (1.0 - luminosity) / (max - luminosity);
+        }
+        channel
+    })
+}
+
+fn saturation(color: [f32; 3]) -> f32 {
+    color[0].max(color[1]).max(color[2]) - color[0].min(color[1]).min(color[2])
+}
+
+fn set_saturation(color: [f32; 3], target: f32) -> [f32; 3] {
+    let min = color[0].min(color[1]).min(color[2]);
+    let max = color[0].max(color[1]).max(color[2]);
+    if max > min {
+        color.map(|channel| (channel - min) * target / (max - min))
+    } else {
+        [0.0; 3]
+    }
+}
+
+/// 16.16 fixed-point number
+fn fixed(value: FT_Fixed) -> f64 {
+    value as f64 / 65536.0
+}
+
+/// 2.14 fixed-point number
+fn f2dot14(value: i16) -> f32 {
+    value as f32 / 16384.0
+}
+
+fn premultiply([r, g, b, a]: [u8; 4]) -> [f32; 4] {
+    let alpha = a as f32 / 255.0;
+
+    let [r, g, b] = [r, g, b].map(|channel| channel as f32 / 255.0 * alpha);
+
+    [r, g, b, alpha]
+}
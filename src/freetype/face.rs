@@ -1,55 +1,102 @@
 use std::{
-    ffi::CString,
+    ops::Range,
     sync::{
         atomic::{AtomicU8, Ordering},
         Arc, Mutex,
     },
 };
 
+use freetype::freetype::{FT_Bitmap, FT_Error, FT_Fixed, FT_Get_Sfnt_Table, FT_Sfnt_Tag};
 use freetype::freetype::{
-    FT_Done_Face, FT_Face, FT_Load_Glyph, FT_New_Face, FT_Render_Glyph, FT_Set_Char_Size,
-    FT_LOAD_NO_BITMAP,
+    FT_Done_Face, FT_Face, FT_Glyph_Metrics, FT_Load_Glyph, FT_Matrix, FT_New_Memory_Face,
+    FT_Render_Glyph, FT_Select_Size, FT_Set_Char_Size, FT_Set_Transform, FT_FACE_FLAG_COLOR,
+    FT_FACE_FLAG_FIXED_SIZES, FT_FACE_FLAG_MULTIPLE_MASTERS, FT_FACE_FLAG_SCALABLE, FT_LOAD_COLOR,
+    FT_LOAD_FORCE_AUTOHINT, FT_LOAD_NO_BITMAP, FT_LOAD_NO_HINTING,
 };
+use freetype::freetype::{FT_Get_Char_Index, FT_Get_First_Char, FT_Get_Next_Char};
+use freetype::freetype::{FT_Glyph_Format, FT_Outline_Translate};
 use freetype::freetype::{FT_Pixel_Mode_, FT_Render_Mode};
+use freetype::freetype::{FT_FACE_FLAG_FIXED_WIDTH, FT_STYLE_FLAG_BOLD, FT_STYLE_FLAG_ITALIC};
+use freetype::tt_os2::TT_OS2;
 
 use crate::{
-    bitmap::{StringBitmap, StringBitmapSize},
-    harfbuzz::shape::Shape,
+    bitmap::StringBitmap,
+    error::{Error, FreeTypeError, Result},
+    harfbuzz::shape::{Orientation, Shape},
+    info::{FontInfo, NameEntry, NameId},
+    metrics::{FontMetrics, GlyphPosition, Rect, TextMetrics},
+    render::{srgb_to_linear, Blending, Color, Hinting, RenderMode, TextStyle},
 };
 
-use super::init::init_freetype;
+use super::{
+    colr::ColorGlyph,
+    colr::Painter,
+    init::{init_freetype, library_version},
+};
 
-/// Handy macro for producing `Err` while handling integer-type error value
-///
-/// ## Usage
-///  - `error_if_not_zero(err)` : Returns `Err(err)` if `err` is not zero, otherwise returns `Ok()`
-///  - `error_if_not_zero(err, ok_value)` : Returns `Err(err)` if `err` is not zero, otherwise returns `Ok(ok_value)`
-macro_rules! error_if_not_zero {
-    ($error_code: expr) => {{
-        if $error_code != 0 {
-            Err($error_code)
-        } else {
-            Ok(())
-        }
-    }};
-    ($error_code: expr, $ok_value: expr) => {{
-        if $error_code != 0 {
-            Err($error_code)
-        } else {
-            Ok($ok_value)
-        }
-    }};
+extern "C" {
+    // Missing in the bindings
+    fn FT_Set_Var_Design_Coordinates(
+        face: FT_Face,
+        num_coords: u32,
+        coords: *mut FT_Fixed,
+    ) -> FT_Error;
 }
 
+/// Leading fields of `TT_Postscript`, which is missing in the bindings
+#[repr(C)]
+struct TT_Postscript {
+    _format_type: FT_Fixed,
+    italic_angle: FT_Fixed,
+}
+
+/// Leading fields of `TT_HoriHeader`, which is missing in the bindings
+#[repr(C)]
+struct TT_HoriHeader {
+    _version: FT_Fixed,
+    ascender: i16,
+    descender: i16,
+    line_gap: i16,
+}
+
+/// Bit of `fsSelection` telling that typo metrics of `OS/2` table are for line spacing
+const USE_TYPO_METRICS: u16 = 1 << 7;
+
+/// Tags of `MVAR` metrics which line extents and font metrics read
+const VARIED_METRICS: [[u8; 4]; 11] = [
+    *b"hasc", *b"hdsc", *b"hlgp", *b"hcla", *b"hcld", *b"xhgt", *b"cpht", *b"strs", *b"stro",
+    *b"unds", *b"undo",
+];
+
+const PIXEL_MODE_MONO: u8 = FT_Pixel_Mode_::FT_PIXEL_MODE_MONO as u8;
+const PIXEL_MODE_GRAY: u8 = FT_Pixel_Mode_::FT_PIXEL_MODE_GRAY as u8;
+const PIXEL_MODE_GRAY2: u8 = FT_Pixel_Mode_::FT_PIXEL_MODE_GRAY2 as u8;
+const PIXEL_MODE_GRAY4: u8 = FT_Pixel_Mode_::FT_PIXEL_MODE_GRAY4 as u8;
+const PIXEL_MODE_LCD: u8 = FT_Pixel_Mode_::FT_PIXEL_MODE_LCD as u8;
+const PIXEL_MODE_LCD_V: u8 = FT_Pixel_Mode_::FT_PIXEL_MODE_LCD_V as u8;
+const PIXEL_MODE_BGRA: u8 = FT_Pixel_Mode_::FT_PIXEL_MODE_BGRA as u8;
+
+/// `FT_RENDER_MODE_SDF` of FreeType 2.11, which is missing in the bindings
+///
+/// Headers of the bindings end with `FT_RENDER_MODE_MAX`, which has the same value.
+const FT_RENDER_MODE_SDF: FT_Render_Mode = FT_Render_Mode::FT_RENDER_MODE_MAX;
+const _: () = assert!(FT_RENDER_MODE_SDF as u32 == 5);
+
 /// Font-face
 ///
 /// # Notes
 /// - This can be cloned with shared access to one FreeType font-face instance internally.
 ///   But it also means that concurrent rendering call to font-face cannot be done in parallel.
-/// - No support for vertical text layout
+/// - Vertical text is laid out on a center line from top to bottom,
+///   with upright glyphs placed by vertical origins from HarfBuzz and sideways glyphs rotated clockwise.
 pub struct FontFace {
     /// Raw pointer
     raw_ptr: FT_Face,
+    /// Font data which FreeType reads from
+    ///
+    /// FreeType doesn't copy memory passed to `FT_New_Memory_Face`,
+    /// so this must outlive `raw_ptr`.
+    data: Arc<[u8]>,
     /// Vertical dpi
     vdpi: u32,
     /// Horizontal dpi
@@ -60,6 +107,30 @@ pub struct FontFace {
     ///
     /// more bigger value, more narrower between letters
     letter_spacing: f64,
+    /// How glyphs are rasterized
+    render_mode: RenderMode,
+    /// Colors of rendered text
+    style: TextStyle,
+    /// Glyph coverage corrected for blending of `style`
+    coverage_table: [u8; 256],
+    /// Number of fractional positions a glyph can be rendered at within a pixel
+    subpixel_bins: u8,
+    /// How outlines are fitted to the pixel grid
+    hinting: Hinting,
+    /// Whether embedded bitmaps are preferred to outlines
+    embedded_bitmaps: bool,
+    /// Index of palette which color glyphs are painted with
+    palette: u16,
+    /// Painter of color glyphs with the palette and the text color, built when first used
+    ///
+    /// It reads the size of the face when painting, so only the colors make it outdated.
+    painter: Option<Painter>,
+    /// Design coordinates of variation axes in 16.16 fixed-point, empty for the default instance
+    variation_coordinates: Vec<FT_Fixed>,
+    /// Positions of `VARIED_METRICS` in font units with variations applied, empty without variations
+    ///
+    /// FreeType leaves metrics of `hhea` table unvaried, so they are read by HarfBuzz.
+    varied_metrics: Vec<([u8; 4], i64)>,
 
     /// Counter of cloned instances and the original
     counter: Arc<AtomicU8>,
@@ -86,13 +157,24 @@ impl Clone for FontFace {
         self.counter.fetch_add(1, Ordering::Relaxed);
 
         Self {
-            raw_ptr: self.raw_ptr.clone(),
-            vdpi: self.vdpi.clone(),
-            hdpi: self.hdpi.clone(),
-            font_size: self.font_size.clone(),
+            raw_ptr: self.raw_ptr,
+            data: self.data.clone(),
+            vdpi: self.vdpi,
+            hdpi: self.hdpi,
+            font_size: self.font_size,
             counter: self.counter.clone(),
             render_mutex: self.render_mutex.clone(),
-            letter_spacing: self.letter_spacing.clone(),
+            letter_spacing: self.letter_spacing,
+            render_mode: self.render_mode,
+            style: self.style,
+            coverage_table: self.coverage_table,
+            subpixel_bins: self.subpixel_bins,
+            hinting: self.hinting,
+            embedded_bitmaps: self.embedded_bitmaps,
+            palette: self.palette,
+            painter: self.painter.clone(),
+            variation_coordinates: self.variation_coordinates.clone(),
+            varied_metrics: self.varied_metrics.clone(),
         }
     }
 }
@@ -100,52 +182,74 @@ impl Clone for FontFace {
 impl FontFace {
     /// Creates FontFace instance with raw pointer
     /// font-size is 20pt by default.
-    fn from_raw_ptr(ptr: FT_Face) -> FontFace {
-        let mut face = FontFace {
+    fn from_raw_ptr(ptr: FT_Face, data: Arc<[u8]>) -> Result<FontFace> {
+        let face = FontFace {
             raw_ptr: ptr,
+            data,
             vdpi: 72,
             hdpi: 72,
             font_size: 20.0,
             letter_spacing: 1.2,
+            render_mode: RenderMode::default(),
+            style: TextStyle::default(),
+            coverage_table: Blending::Srgb.coverage_table(Color::WHITE),
+            subpixel_bins: 4,
+            hinting: Hinting::default(),
+            embedded_bitmaps: false,
+            palette: 0,
+            painter: None,
+            variation_coordinates: Vec::new(),
+            varied_metrics: Vec::new(),
             counter: Arc::new(AtomicU8::new(1)),
             render_mutex: Arc::new(Mutex::new(false)),
         };
-        face.call_ft_set_chart_size()
-            .expect("Failed to set FontFace to default dpi/size");
+        // Dropping `face` releases the raw pointer on failure
+        face.call_ft_set_chart_size()?;
 
-        face
+        Ok(face)
     }
 
-    /// Creates FontFace instance from font file
-    pub fn from_file(filename: &str, face_index: i64) -> Result<FontFace, i32> {
-        let library = {
-            let library_init_result = init_freetype();
-            if let Ok(ptr_wrapper) = library_init_result {
-                ptr_wrapper.ptr
-            } else if let Err(err) = library_init_result {
-                return Err(*err);
-            } else {
-                unreachable!();
-            }
+    /// Creates FontFace instance from font data in memory
+    pub fn from_bytes(data: Arc<[u8]>, face_index: u32) -> Result<FontFace> {
+        let raw_face_ptr = new_memory_face(&data, face_index as i64)?;
+
+        FontFace::from_raw_ptr(raw_face_ptr, data)
+    }
+
+    /// Number of faces in font data, which is more than one for collections such as TTC and DFONT
+    pub fn face_count(data: &[u8]) -> Result<u32> {
+        // Face of negative index only tells about the file, and has no glyphs to load
+        let raw_face_ptr = new_memory_face(data, -1)?;
+        let count = unsafe {
+            let count = (*raw_face_ptr).num_faces;
+            FT_Done_Face(raw_face_ptr);
+            count
         };
 
-        unsafe {
-            let mut raw_face_ptr = std::ptr::null_mut();
-            let filename_c_str =
-                CString::new(filename).expect("Failed to create CString from filename");
-            let err = FT_New_Face(
-                library,
-                filename_c_str.as_ptr(),
-                face_index,
-                &mut raw_face_ptr,
-            );
+        Ok(count as u32)
+    }
 
-            error_if_not_zero!(err, FontFace::from_raw_ptr(raw_face_ptr))
+    /// Sets variations, dpi and font-size of FT_Face
+    ///
+    /// Fonts drawn from bitmap strikes select the best strike instead.
+    fn call_ft_set_chart_size(&self) -> Result<()> {
+        if self.has_face_flag(FT_FACE_FLAG_MULTIPLE_MASTERS) {
+            // FreeType does nothing when coordinates are unchanged
+            let err = unsafe {
+                FT_Set_Var_Design_Coordinates(
+                    self.raw_ptr,
+                    self.variation_coordinates.len() as u32,
+                    self.variation_coordinates.as_ptr() as *mut _,
+                )
+            };
+            error_if_not_zero!(err)?;
+        }
+
+        if let Some(strike_index) = self.best_strike() {
+            let err = unsafe { FT_Select_Size(self.raw_ptr, strike_index) };
+            return error_if_not_zero!(err);
         }
-    }
 
-    /// Sets dpi and font-size of FT_Face
-    fn call_ft_set_chart_size(&mut self) -> Result<(), i32> {
         unsafe {
             let err = FT_Set_Char_Size(
                 self.raw_ptr,
@@ -159,11 +263,93 @@ impl FontFace {
         }
     }
 
+    fn has_face_flag(&self, flag: u32) -> bool {
+        unsafe { (*self.raw_ptr).face_flags as u32 & flag != 0 }
+    }
+
+    /// Whether glyphs are drawn from bitmap strikes, which cannot be set to arbitrary size
+    ///
+    /// Bitmap-only fonts always are, and color fonts with strikes are when embedded bitmaps are preferred.
+    fn uses_strikes(&self) -> bool {
+        self.has_face_flag(FT_FACE_FLAG_FIXED_SIZES)
+            && (!self.has_face_flag(FT_FACE_FLAG_SCALABLE)
+                || (self.embedded_bitmaps && self.has_face_flag(FT_FACE_FLAG_COLOR)))
+    }
+
+    /// Whether bitmaps of strikes are scaled to the requested size
+    ///
+    /// Only color bitmaps are scaled, as pixel fonts look best at their own size.
+    fn scales_strikes(&self) -> bool {
+        self.uses_strikes() && self.has_face_flag(FT_FACE_FLAG_COLOR)
+    }
+
+    /// Requested size of em in pixels, horizontally and vertically
+    fn requested_ppem(&self) -> (f64, f64) {
+        let size = self.font_size as f64;
+
+        (
+            size * self.hdpi as f64 / 72.0,
+            size * self.vdpi as f64 / 72.0,
+        )
+    }
+
+    /// Index of strike closest to the requested size, if glyphs are drawn from strikes
+    ///
+    /// Color bitmaps are scaled down from the smallest strike not smaller than requested,
+    /// or scaled up from the largest one.
+    fn best_strike(&self) -> Option<i32> {
+        if !self.uses_strikes() {
+            return None;
+        }
+
+        let strikes = unsafe {
+            std::slice::from_raw_parts(
+                (*self.raw_ptr).available_sizes,
+                (*self.raw_ptr).num_fixed_sizes as usize,
+            )
+        };
+        let requested = self.requested_ppem().1 * 64.0;
+        let ppems = strikes
+            .iter()
+            .map(|strike| strike.y_ppem as f64)
+            .enumerate();
+        let best = if self.scales_strikes() {
+            ppems
+                .clone()
+                .filter(|(_, ppem)| *ppem >= requested)
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .or_else(|| ppems.max_by(|(_, a), (_, b)| a.total_cmp(b)))
+        } else {
+            ppems.min_by(|(_, a), (_, b)| (a - requested).abs().total_cmp(&(b - requested).abs()))
+        };
+
+        best.map(|(index, _)| index as i32)
+    }
+
+    /// Ratio of the requested size to the size of selected strike
+    fn strike_scale(&self) -> f64 {
+        if !self.scales_strikes() {
+            return 1.0;
+        }
+
+        let strike_ppem = unsafe { (*(*self.raw_ptr).size).metrics.y_ppem };
+        if strike_ppem == 0 {
+            1.0
+        } else {
+            self.requested_ppem().1 / strike_ppem as f64
+        }
+    }
+
     /// Sets font size in pt unit
     pub fn set_font_size(&mut self, size_in_pt: f32) {
         self.font_size = size_in_pt;
     }
 
+    /// Font size in pt unit
+    pub fn font_size(&self) -> f32 {
+        self.font_size
+    }
+
     /// Sets dpi
     ///
     /// - `hdpi` : Horizontal dpi
@@ -173,65 +359,533 @@ impl FontFace {
         self.vdpi = vdpi;
     }
 
-    fn render_glpyh_with_index(&mut self, glyph_index: u32) -> Result<(), i32> {
+    /// Renders glyph whose origin is at (`origin_x`, `origin_y`) in 26.6 pixels, y going down
+    ///
+    /// Returns origin in whole pixels, which the rendered bitmap is relative to.
+    fn render_glpyh_with_index(
+        &mut self,
+        glyph_index: u32,
+        origin_x: i64,
+        origin_y: i64,
+    ) -> Result<(i64, i64)> {
         self.load_glpyh_with_index(glyph_index)?;
+        self.render_loaded_glyph(origin_x, origin_y)
+    }
+
+    /// Renders glyph rotated 90 degrees clockwise, for sideways glyphs in vertical text
+    fn render_rotated_glpyh_with_index(
+        &mut self,
+        glyph_index: u32,
+        origin_x: i64,
+        origin_y: i64,
+    ) -> Result<(i64, i64)> {
+        // 16.16 fixed-point matrix mapping (x, y) to (y, -x)
+        let mut matrix = FT_Matrix {
+            xx: 0,
+            xy: 0x10000,
+            yx: -0x10000,
+            yy: 0,
+        };
+        unsafe {
+            FT_Set_Transform(self.raw_ptr, &mut matrix, std::ptr::null_mut());
+        }
+        // Transform is applied while loading
+        let result = self.load_glpyh_with_index(glyph_index);
         unsafe {
-            let err = FT_Render_Glyph((*self.raw_ptr).glyph, FT_Render_Mode::FT_RENDER_MODE_LCD);
+            FT_Set_Transform(self.raw_ptr, std::ptr::null_mut(), std::ptr::null_mut());
+        }
+        result?;
 
-            error_if_not_zero!(err)
+        self.render_loaded_glyph(origin_x, origin_y)
+    }
+
+    /// Renders loaded glyph, moving its outline by fractional part of origin
+    fn render_loaded_glyph(&mut self, origin_x: i64, origin_y: i64) -> Result<(i64, i64)> {
+        let (pixel_x, offset_x) = self.snap_to_subpixel_bin(origin_x);
+        let (pixel_y, offset_y) = self.snap_to_subpixel_bin(origin_y);
+        unsafe {
+            let glyph = (*self.raw_ptr).glyph;
+            if (*glyph).format == FT_Glyph_Format::FT_GLYPH_FORMAT_OUTLINE {
+                // Outline goes upward
+                FT_Outline_Translate(&(*glyph).outline, offset_x, -offset_y);
+            }
+            let err = FT_Render_Glyph(glyph, raw_render_mode(self.render_mode)?);
+
+            error_if_not_zero!(err, (pixel_x, pixel_y))
         }
     }
 
-    fn load_glpyh_with_index(&mut self, glyph_index: u32) -> Result<(), i32> {
+    /// Splits 26.6 position into whole pixels and fraction rounded to the nearest subpixel bin
+    fn snap_to_subpixel_bin(&self, position: i64) -> (i64, i64) {
+        let bins = self.subpixel_bins as i64;
+        let bin = ((position & 63) * bins + 32) / 64;
+
+        ((position >> 6) + bin / bins, (bin % bins) * 64 / bins)
+    }
+
+    fn load_glpyh_with_index(&mut self, glyph_index: u32) -> Result<()> {
         unsafe {
-            let err = FT_Load_Glyph(
-                self.raw_ptr,
-                glyph_index,
-                FT_LOAD_NO_BITMAP.try_into().unwrap(),
-            );
+            let err = FT_Load_Glyph(self.raw_ptr, glyph_index, self.load_flags());
 
             error_if_not_zero!(err)
         }
     }
 
-    /// Measure size of rendered string
-    fn measure_size_without_lock(&mut self, shapes: &[Shape]) -> Result<StringBitmapSize, i32> {
-        let mut ymin = 0;
-        let mut ymax = 0;
+    /// Size of em in 26.6 pixels, horizontally and vertically
+    fn em_size(&self) -> (f64, f64) {
+        let (metrics, upem) = unsafe {
+            (
+                (*(*self.raw_ptr).size).metrics,
+                (*self.raw_ptr).units_per_EM,
+            )
+        };
+        if upem == 0 || self.uses_strikes() {
+            // Bitmap-only fonts have no design units, and strikes are scaled as a whole
+            let scale = self.strike_scale() * 64.0;
+            return (metrics.x_ppem as f64 * scale, metrics.y_ppem as f64 * scale);
+        }
+
+        (
+            metrics.x_scale as f64 * upem as f64 / 65536.0,
+            metrics.y_scale as f64 * upem as f64 / 65536.0,
+        )
+    }
+
+    /// Horizontal advance of glyph in 26.6 pixels
+    fn x_advance(&self, shape: &Shape) -> i64 {
+        let scale = shape.scale.0 as f64 * self.letter_spacing;
+
+        (shape.x_advance as f64 * self.em_size().0 / scale).round() as i64
+    }
+
+    /// Vertical advance of glyph in 26.6 pixels, going upward as in HarfBuzz
+    fn y_advance(&self, shape: &Shape) -> i64 {
+        let scale = shape.scale.1 as f64 * self.letter_spacing;
+
+        (shape.y_advance as f64 * self.em_size().1 / scale).round() as i64
+    }
+
+    /// Downward advance of glyph in vertical text in 26.6 pixels
+    fn vertical_advance(&self, shape: &Shape) -> i64 {
+        match shape.orientation {
+            Orientation::Sideways => self.x_advance(shape),
+            _ => -self.y_advance(shape),
+        }
+    }
+
+    /// HarfBuzz offsets of glyph from pen position in 26.6 pixels, y going up
+    fn offsets(&self, shape: &Shape) -> (i64, i64) {
+        let (x_em, y_em) = self.em_size();
+
+        (
+            (shape.x_offset as f64 * x_em / shape.scale.0 as f64).round() as i64,
+            (shape.y_offset as f64 * y_em / shape.scale.1 as f64).round() as i64,
+        )
+    }
+
+    /// Horizontal offset of baseline of sideways glyphs from the center line in 26.6 pixels
+    ///
+    /// Ascender goes to the right of baseline, so that the em box is centered.
+    fn sideways_baseline(&self) -> i64 {
+        let metrics = unsafe { (*(*self.raw_ptr).size).metrics };
+
+        -self.scale_to_strike(metrics.ascender + metrics.descender) / 2
+    }
+
+    /// Scales 26.6 length of selected strike to the requested size
+    fn scale_to_strike(&self, length: i64) -> i64 {
+        (length as f64 * self.strike_scale()).round() as i64
+    }
+
+    /// Loads glyph and returns its metrics
+    ///
+    /// Metrics of color glyphs cover what is painted, rather than the outline of the glyph.
+    fn load_glyph_metrics(&mut self, glyph_index: u32) -> Result<FT_Glyph_Metrics> {
+        self.update_painter()?;
+        let bounds = match &self.painter {
+            Some(painter) => painter.bounds(glyph_index)?,
+            None => None,
+        };
+        self.load_glpyh_with_index(glyph_index)?;
+        let mut metrics = self.glyph_metrics();
+
+        if let Some((x_min, y_min, x_max, y_max)) = bounds {
+            // Vertical origin stays where it is from the horizontal origin
+            let vertical_origin_x = metrics.vertBearingX - metrics.horiBearingX;
+            let vertical_origin_y = metrics.vertBearingY + metrics.horiBearingY;
+            metrics.horiBearingX = (x_min * 64.0).floor() as i64;
+            metrics.horiBearingY = (y_max * 64.0).ceil() as i64;
+            metrics.width = (x_max * 64.0).ceil() as i64 - metrics.horiBearingX;
+            metrics.height = metrics.horiBearingY - (y_min * 64.0).floor() as i64;
+            metrics.vertBearingX = metrics.horiBearingX + vertical_origin_x;
+            metrics.vertBearingY = vertical_origin_y - metrics.horiBearingY;
+        }
+
+        Ok(metrics)
+    }
+
+    /// Metrics of loaded glyph in 26.6 pixels, scaled along with its bitmap
+    fn glyph_metrics(&self) -> FT_Glyph_Metrics {
+        let mut metrics = unsafe { (*(*self.raw_ptr).glyph).metrics };
+        for length in [
+            &mut metrics.width,
+            &mut metrics.height,
+            &mut metrics.horiBearingX,
+            &mut metrics.horiBearingY,
+            &mut metrics.horiAdvance,
+            &mut metrics.vertBearingX,
+            &mut metrics.vertBearingY,
+            &mut metrics.vertAdvance,
+        ] {
+            *length = self.scale_to_strike(*length);
+        }
+
+        metrics
+    }
+
+    /// Measures advance, boxes and glyph positions of string
+    ///
+    /// `vertical` tells the orientation of strings without any glyph.
+    fn text_metrics_without_lock(
+        &mut self,
+        shapes: &[Shape],
+        vertical: bool,
+    ) -> Result<TextMetrics> {
+        let vertical = vertical || is_vertical(shapes);
+        let sideways_baseline = self.sideways_baseline();
+        let to_pixels = |length: i64| length as f32 / 64.0;
+
+        let mut glyphs = Vec::with_capacity(shapes.len());
+        // Ink of glyphs in 26.6 pixels as `(x_min, y_min, x_max, y_max)`, y going up
+        let mut ink: Option<(i64, i64, i64, i64)> = None;
         let mut pen_x = 0;
+        let mut pen_y = 0;
         for shape in shapes {
-            self.load_glpyh_with_index(shape.glyph_id)?;
-            let metrics = unsafe { (*(*self.raw_ptr).glyph).metrics };
-            let height = metrics.height;
-            let horizontal_bearing_y = metrics.horiBearingY;
-            ymin = std::cmp::max(ymin, height - horizontal_bearing_y);
-            ymax = std::cmp::max(ymax, horizontal_bearing_y);
-            let scale =
-                unsafe { (shape.scale as f64) / (*(*self.raw_ptr).size).metrics.x_ppem as f64 }
-                    * self.letter_spacing;
-            pen_x += (shape.x_advance as f64 / scale) as i64;
-        }
-
-        let width = pen_x/* - last_horizontal_advance + last_char_width */;
-        Ok(StringBitmapSize {
-            width: (width as u64),
-            height: ((ymax + ymin) as u64 >> 6) + 1,
-            y_min: ymin as u64 >> 6,
-            y_max: ymax as u64 >> 6,
-        })
+            let metrics = self.load_glyph_metrics(shape.glyph_id)?;
+            let (x_offset, y_offset) = self.offsets(shape);
+            // Glyph origin and ink relative to the origin of text
+            let (origin, glyph_ink) = if !vertical {
+                let (x, y) = (pen_x + x_offset, pen_y + y_offset);
+                let left = x + metrics.horiBearingX;
+                let top = y + metrics.horiBearingY;
+                (
+                    (x, y),
+                    (left, top - metrics.height, left + metrics.width, top),
+                )
+            } else if shape.orientation == Orientation::Sideways {
+                // Glyph is turned clockwise, so its ascender goes to the right
+                let (x, y) = (sideways_baseline + y_offset, -pen_y - x_offset);
+                let right = x + metrics.horiBearingY;
+                let top = y - metrics.horiBearingX;
+                (
+                    (x, y),
+                    (right - metrics.height, top - metrics.width, right, top),
+                )
+            } else {
+                let (x, y) = (x_offset, -pen_y + y_offset);
+                let left = x + metrics.horiBearingX;
+                let top = y + metrics.horiBearingY;
+                (
+                    (x, y),
+                    (left, top - metrics.height, left + metrics.width, top),
+                )
+            };
+            if metrics.width > 0 && metrics.height > 0 {
+                let (x_min, y_min, x_max, y_max) = glyph_ink;
+                ink = Some(match ink {
+                    Some((left, bottom, right, top)) => (
+                        left.min(x_min),
+                        bottom.min(y_min),
+                        right.max(x_max),
+                        top.max(y_max),
+                    ),
+                    None => glyph_ink,
+                });
+            }
+
+            let advance = if vertical {
+                self.vertical_advance(shape)
+            } else {
+                self.x_advance(shape)
+            };
+            glyphs.push(GlyphPosition {
+                glyph_id: shape.glyph_id,
+                cluster: shape.cluster,
+                x: to_pixels(origin.0),
+                y: to_pixels(origin.1),
+                advance: to_pixels(advance),
+            });
+            if vertical {
+                pen_y += advance;
+            } else {
+                pen_x += advance;
+                pen_y += self.y_advance(shape);
+            }
+        }
+
+        let advance = if vertical { pen_y } else { pen_x };
+        let (ascender, descender, _) = self.line_extents();
+        let logical_box = if vertical {
+            // Line height centered on the center line, as sideways glyphs are
+            let half = ((ascender - descender) / 2.0) as f32;
+            Rect {
+                x_min: -half,
+                y_min: -to_pixels(advance),
+                x_max: half,
+                y_max: 0.0,
+            }
+        } else {
+            Rect {
+                x_min: 0.0,
+                y_min: descender as f32,
+                x_max: to_pixels(advance),
+                y_max: ascender as f32,
+            }
+        };
+        let (padding_x, padding_y) = self.raster_padding();
+        let to_rect =
+            |(x_min, y_min, x_max, y_max): (i64, i64, i64, i64), (x, y): (i64, i64)| Rect {
+                x_min: to_pixels(x_min - x),
+                y_min: to_pixels(y_min - y),
+                x_max: to_pixels(x_max + x),
+                y_max: to_pixels(y_max + y),
+            };
+        let ink_box = ink.map(|ink| to_rect(ink, (0, 0)));
+        let render_box = ink.map_or(logical_box, |ink| to_rect(ink, (padding_x, padding_y)));
+
+        Ok(TextMetrics::new(
+            to_pixels(advance),
+            logical_box,
+            ink_box,
+            render_box,
+            glyphs,
+            vertical,
+        ))
+    }
+
+    /// What rasterization adds around outlines in 26.6 pixels, horizontally and vertically
+    fn raster_padding(&self) -> (i64, i64) {
+        match self.render_mode {
+            // LCD filter spreads coverage into subpixels of neighbouring pixels
+            RenderMode::Lcd => (64, 0),
+            RenderMode::LcdV => (0, 64),
+            // Default spread of distance fields in FreeType
+            RenderMode::Sdf => (8 * 64, 8 * 64),
+            RenderMode::Normal | RenderMode::Mono => (0, 0),
+        }
+    }
+
+    /// Measures advance, logical and ink boxes and glyph positions of string
+    ///
+    /// `vertical` tells the orientation of strings without any glyph.
+    pub fn text_metrics(&mut self, shapes: &[Shape], vertical: bool) -> Result<TextMetrics> {
+        // Protect this method as critical section
+        let mutex_cloned = self.render_mutex.clone();
+        let _guard = mutex_cloned.lock();
+
+        self.call_ft_set_chart_size()?;
+        self.text_metrics_without_lock(shapes, vertical)
     }
 
-    /// Measure size of rendered string
-    pub fn measure_size(&mut self, shapes: &[Shape]) -> Result<StringBitmapSize, i32> {
+    /// Horizontal advance of each glyph in 26.6 fixed-point pixels
+    ///
+    /// Sum of advances equals to the advance from `text_metrics`.
+    pub fn advances(&mut self, shapes: &[Shape]) -> Result<Vec<i64>> {
         // Protect this method as critical section
         let mutex_cloned = self.render_mutex.clone();
         let _guard = mutex_cloned.lock();
 
         self.call_ft_set_chart_size()?;
-        self.measure_size_without_lock(shapes)
+        Ok(shapes.iter().map(|shape| self.x_advance(shape)).collect())
     }
 
-    pub fn get_ppem(&mut self) -> Result<(u16, u16), i32> {
+    /// Ascender and line height of the font in pixels
+    pub fn line_metrics(&mut self) -> Result<(i64, i64)> {
+        // Protect this method as critical section
+        let mutex_cloned = self.render_mutex.clone();
+        let _guard = mutex_cloned.lock();
+
+        self.call_ft_set_chart_size()?;
+        let (ascender, descender, line_gap) = self.line_extents();
+        Ok((
+            ascender.ceil() as i64,
+            (ascender - descender + line_gap).ceil() as i64,
+        ))
+    }
+
+    /// Metrics of the font at the current size
+    pub fn metrics(&mut self) -> Result<FontMetrics> {
+        // Protect this method as critical section
+        let mutex_cloned = self.render_mutex.clone();
+        let _guard = mutex_cloned.lock();
+
+        self.call_ft_set_chart_size()?;
+        let (ascender, descender, line_gap) = self.line_extents();
+        let em = (self.em_size().1 / 64.0) as f32;
+        let (os2, face) = unsafe { (self.os2(), *self.raw_ptr) };
+        let (mut x_height, mut cap_height) = (0.0, 0.0);
+        let (mut strikeout_position, mut strikeout_thickness) = (0.0, 0.0);
+        if let Some(os2) = os2 {
+            // Heights are in version 2 and later
+            if os2.version >= 2 {
+                x_height = self.units_to_pixels(self.varied_metric(b"xhgt", os2.sxHeight.into()));
+                cap_height =
+                    self.units_to_pixels(self.varied_metric(b"cpht", os2.sCapHeight.into()));
+            }
+            strikeout_thickness =
+                self.units_to_pixels(self.varied_metric(b"strs", os2.yStrikeoutSize.into()));
+            // Position is the top of the stroke
+            strikeout_position = self
+                .units_to_pixels(self.varied_metric(b"stro", os2.yStrikeoutPosition.into()))
+                - strikeout_thickness / 2.0;
+        }
+        if x_height <= 0.0 {
+            x_height = self.ink_top('x')?.unwrap_or(em / 2.0);
+        }
+        if cap_height <= 0.0 {
+            cap_height = self.ink_top('H')?.unwrap_or(em * 0.7);
+        }
+
+        // FreeType moves the position of `post` table to the center of the stroke
+        let (position, thickness) =
+            match (self.varied_position(b"undo"), self.varied_position(b"unds")) {
+                (Some(position), Some(thickness)) => (position - thickness / 2, thickness),
+                _ => (
+                    face.underline_position.into(),
+                    face.underline_thickness.into(),
+                ),
+            };
+        let mut underline_position = self.units_to_pixels(position);
+        let mut underline_thickness = self.units_to_pixels(thickness);
+        if underline_thickness <= 0.0 {
+            underline_position = -em / 10.0;
+            underline_thickness = em / 20.0;
+        }
+        if strikeout_thickness <= 0.0 {
+            strikeout_thickness = underline_thickness;
+            strikeout_position = x_height / 2.0;
+        }
+
+        let max_advance = if face.units_per_EM == 0 {
+            let size_metrics = unsafe { (*face.size).metrics };
+            self.scale_to_strike(size_metrics.max_advance) as f32 / 64.0
+        } else {
+            self.units_to_pixels(face.max_advance_width.into())
+        };
+
+        Ok(FontMetrics {
+            ascender: ascender as f32,
+            descender: descender as f32,
+            line_gap: line_gap as f32,
+            x_height,
+            cap_height,
+            underline_position,
+            underline_thickness,
+            strikeout_position,
+            strikeout_thickness,
+            max_advance,
+        })
+    }
+
+    /// Ascender, descender and line gap in pixels, y going up
+    ///
+    /// Typo metrics are used if `USE_TYPO_METRICS` is set, then `hhea` metrics unless they are zeros,
+    /// and then win metrics, all with variations applied.
+    /// Bitmap-only fonts have metrics of the selected strike.
+    fn line_extents(&self) -> (f64, f64, f64) {
+        let face = unsafe { *self.raw_ptr };
+        if face.units_per_EM == 0 {
+            let metrics = unsafe { (*face.size).metrics };
+            let ascender = self.scale_to_strike(metrics.ascender) as f64 / 64.0;
+            let descender = self.scale_to_strike(metrics.descender) as f64 / 64.0;
+            let height = self.scale_to_strike(metrics.height) as f64 / 64.0;
+            return (ascender, descender, height - ascender + descender);
+        }
+
+        let os2 = unsafe { self.os2() };
+        let hhea = unsafe {
+            let hhea =
+                FT_Get_Sfnt_Table(self.raw_ptr, FT_Sfnt_Tag::FT_SFNT_HHEA) as *const TT_HoriHeader;
+            hhea.as_ref()
+        };
+        let (ascender, descender, line_gap): (i64, i64, i64) = match (os2, hhea) {
+            (Some(os2), _) if os2.fsSelection & USE_TYPO_METRICS != 0 => (
+                self.varied_metric(b"hasc", os2.sTypoAscender.into()),
+                self.varied_metric(b"hdsc", os2.sTypoDescender.into()),
+                self.varied_metric(b"hlgp", os2.sTypoLineGap.into()),
+            ),
+            (_, Some(hhea)) if hhea.ascender != 0 || hhea.descender != 0 => (
+                self.varied_metric(b"hasc", hhea.ascender.into()),
+                self.varied_metric(b"hdsc", hhea.descender.into()),
+                self.varied_metric(b"hlgp", hhea.line_gap.into()),
+            ),
+            (Some(os2), _) => (
+                self.varied_metric(b"hcla", os2.usWinAscent.into()),
+                -self.varied_metric(b"hcld", os2.usWinDescent.into()),
+                0,
+            ),
+            // FreeType has its own guess for fonts without both tables, such as Type 1 fonts
+            (None, _) => (
+                face.ascender.into(),
+                face.descender.into(),
+                (face.height - face.ascender + face.descender).into(),
+            ),
+        };
+        let to_pixels = |units: i64| self.units_to_pixels(units) as f64;
+
+        (
+            to_pixels(ascender),
+            to_pixels(descender),
+            to_pixels(line_gap),
+        )
+    }
+
+    /// `OS/2` table of the face, if it has any
+    unsafe fn os2(&self) -> Option<&TT_OS2> {
+        let os2 = FT_Get_Sfnt_Table(self.raw_ptr, FT_Sfnt_Tag::FT_SFNT_OS2) as *const TT_OS2;
+        // FreeType marks missing table of TrueType fonts with version 0xFFFF
+        os2.as_ref().filter(|os2| os2.version != 0xFFFF)
+    }
+
+    /// Position of `MVAR` metric `tag` in font units, or `None` for the default instance
+    fn varied_position(&self, tag: &[u8; 4]) -> Option<i64> {
+        self.varied_metrics
+            .iter()
+            .find(|(other, _)| other == tag)
+            .map(|(_, position)| *position)
+    }
+
+    /// Position of `MVAR` metric `tag` in font units, or `default` read from unvaried tables
+    fn varied_metric(&self, tag: &[u8; 4], default: i64) -> i64 {
+        self.varied_position(tag).unwrap_or(default)
+    }
+
+    /// Converts length in font units to pixels at the current size
+    fn units_to_pixels(&self, units: i64) -> f32 {
+        let upem = unsafe { (*self.raw_ptr).units_per_EM };
+        if upem == 0 {
+            return 0.0;
+        }
+
+        (units as f64 * self.em_size().1 / 64.0 / upem as f64) as f32
+    }
+
+    /// Top of the ink of the glyph for `c` in pixels, `None` if the font has no glyph for it
+    fn ink_top(&mut self, c: char) -> Result<Option<f32>> {
+        let glyph_index = unsafe { FT_Get_Char_Index(self.raw_ptr, c as _) };
+        if glyph_index == 0 {
+            return Ok(None);
+        }
+
+        let metrics = self.load_glyph_metrics(glyph_index)?;
+        Ok(Some(metrics.horiBearingY as f32 / 64.0))
+    }
+
+    pub fn get_ppem(&mut self) -> Result<(u16, u16)> {
+        // Protect this method as critical section
+        let mutex_cloned = self.render_mutex.clone();
+        let _guard = mutex_cloned.lock();
+
         self.call_ft_set_chart_size()?;
         Ok(unsafe {
             (
@@ -242,90 +896,651 @@ impl FontFace {
     }
 
     /// Renders string
-    pub fn render_string(&mut self, shapes: &[Shape]) -> Result<StringBitmap, i32> {
+    pub fn render_string(&mut self, shapes: &[Shape]) -> Result<StringBitmap> {
         // Protect this method as critical section
         let mutex_cloned = self.render_mutex.clone();
         let _guard = mutex_cloned.lock();
 
         self.call_ft_set_chart_size()?;
-        let size = self.measure_size_without_lock(shapes)?;
+        let size = self.text_metrics_without_lock(shapes, false)?.bitmap_size();
 
-        let mut result = StringBitmap::new(size);
-        let mut pen_x: i64 = 0;
-        let mut pen_y = 0;
+        // Origin is left of the left edge by overhang of glyphs with negative bearing
+        let mut result = StringBitmap::new(size, &self.style)?;
+        let (origin_x, origin_y) = (size.x_min as i64, size.y_max as i64);
+        if is_vertical(shapes) {
+            self.draw_vertical_string_without_lock(shapes, &mut result, origin_x, origin_y << 6)?;
+        } else {
+            self.draw_string_without_lock(shapes, &mut result, origin_x << 6, origin_y)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Draws string into bitmap, which can be shared with other font-faces
+    ///
+    /// Glyphs are drawn from `pen_x` on `baseline`, which is row index in `result`.
+    /// `pen_x` is in 26.6 fixed-point pixels, so that glyphs can be placed at fractional positions.
+    /// Returns pen position after the last glyph.
+    pub fn draw_string(
+        &mut self,
+        shapes: &[Shape],
+        result: &mut StringBitmap,
+        pen_x: i64,
+        baseline: i64,
+    ) -> Result<i64> {
+        // Protect this method as critical section
+        let mutex_cloned = self.render_mutex.clone();
+        let _guard = mutex_cloned.lock();
+
+        self.call_ft_set_chart_size()?;
+        self.draw_string_without_lock(shapes, result, pen_x, baseline)
+    }
+
+    fn draw_string_without_lock(
+        &mut self,
+        shapes: &[Shape],
+        result: &mut StringBitmap,
+        mut pen_x: i64,
+        baseline: i64,
+    ) -> Result<i64> {
+        let mut pen_y = baseline << 6;
 
         for shape in shapes {
-            self.render_glpyh_with_index(shape.glyph_id)?;
-            let bitmap = unsafe { (*(*self.raw_ptr).glyph).bitmap };
-            let has_alpha = bitmap.pixel_mode != (FT_Pixel_Mode_::FT_PIXEL_MODE_BGRA) as u8;
+            let (x_offset, y_offset) = self.offsets(shape);
+            self.draw_glyph_with_index(
+                result,
+                shape.glyph_id,
+                pen_x + x_offset,
+                pen_y - y_offset,
+                false,
+            )?;
+
+            pen_x += self.x_advance(shape);
+            pen_y -= self.y_advance(shape);
+        }
+
+        Ok(pen_x)
+    }
+
+    /// Draws glyph whose origin is at (`origin_x`, `origin_y`) in 26.6 pixels, y going down
+    ///
+    /// `rotated` turns the glyph 90 degrees clockwise, for sideways glyphs in vertical text.
+    /// Color glyphs are painted in colors of the selected palette.
+    fn draw_glyph_with_index(
+        &mut self,
+        result: &mut StringBitmap,
+        glyph_index: u32,
+        origin_x: i64,
+        origin_y: i64,
+        rotated: bool,
+    ) -> Result<()> {
+        self.update_painter()?;
+        if let Some(painter) = &self.painter {
+            let (pixel_x, offset_x) = self.snap_to_subpixel_bin(origin_x);
+            let (pixel_y, offset_y) = self.snap_to_subpixel_bin(origin_y);
+            let (offset_x, offset_y) = (offset_x as f64 / 64.0, offset_y as f64 / 64.0);
+            if let Some(glyph) = painter.paint(glyph_index, offset_x, offset_y, rotated)? {
+                self.draw_painted_glyph(result, &glyph, pixel_x, pixel_y);
+                return Ok(());
+            }
+        }
+
+        let (pixel_x, pixel_y) = if rotated {
+            self.render_rotated_glpyh_with_index(glyph_index, origin_x, origin_y)?
+        } else {
+            self.render_glpyh_with_index(glyph_index, origin_x, origin_y)?
+        };
+        self.draw_glyph(result, pixel_x, pixel_y)
+    }
+
+    /// Builds painter of color glyphs unless it is built or the face has none
+    fn update_painter(&mut self) -> Result<()> {
+        if self.painter.is_some() || !self.has_face_flag(FT_FACE_FLAG_COLOR) {
+            return Ok(());
+        }
+
+        let library = match init_freetype() {
+            Ok(ptr_wrapper) => ptr_wrapper.ptr,
+            Err(err) => return Err(err.clone()),
+        };
+        self.painter = Some(Painter::new(
+            self.raw_ptr,
+            library,
+            self.palette,
+            self.style.foreground,
+        ));
+
+        Ok(())
+    }
+
+    /// Draws painted color glyph, whose origin is at (`origin_x`, `origin_y`) of `result`
+    fn draw_painted_glyph(
+        &self,
+        result: &mut StringBitmap,
+        glyph: &ColorGlyph,
+        origin_x: i64,
+        origin_y: i64,
+    ) {
+        for (index, pixel) in glyph.pixels.iter().enumerate() {
+            let x = (index % glyph.width) as i64 + glyph.left;
+            let y = (index / glyph.width) as i64 + glyph.top;
+            self.composite_color(result, origin_x + x, origin_y + y, *pixel);
+        }
+    }
+
+    /// Composites premultiplied color with blending of style
+    fn composite_color(&self, result: &mut StringBitmap, x: i64, y: i64, rgba: [f32; 4]) {
+        let alpha = rgba[3];
+        if alpha <= 0.0 {
+            return;
+        }
+
+        let linear = self.style.blending == Blending::Linear;
+        let color = [rgba[0], rgba[1], rgba[2]].map(|channel| {
+            if linear {
+                let straight = (channel / alpha * 255.0).round().min(255.0);
+                srgb_to_linear(straight as u8) * alpha
+            } else {
+                channel
+            }
+        });
+
+        result.composite(x, y, color, [alpha; 3], linear);
+    }
 
-            if bitmap.pixel_mode != (FT_Pixel_Mode_::FT_PIXEL_MODE_LCD) as u8 && has_alpha {
-                panic!("Non-suppported font: No RGB/RGBA Rendering available");
+    /// Draws the rendered glyph slot, whose origin is at (`origin_x`, `origin_y`) of `result`
+    fn draw_glyph(&self, result: &mut StringBitmap, origin_x: i64, origin_y: i64) -> Result<()> {
+        let (bitmap, bitmap_left, bitmap_top) = unsafe {
+            let glyph = *(*self.raw_ptr).glyph;
+            (glyph.bitmap, glyph.bitmap_left, glyph.bitmap_top)
+        };
+        if bitmap.pixel_mode == PIXEL_MODE_BGRA {
+            self.draw_color_glyph(result, origin_x, origin_y);
+            return Ok(());
+        }
+        if self.render_mode == RenderMode::Sdf && bitmap.pixel_mode == PIXEL_MODE_GRAY {
+            draw_distance_field(
+                result,
+                &bitmap,
+                origin_x + bitmap_left as i64,
+                origin_y - bitmap_top as i64,
+            );
+            return Ok(());
+        }
+
+        // Subpixels take three bytes in a row or three rows for each pixel
+        let (width, rows) = match bitmap.pixel_mode {
+            PIXEL_MODE_MONO | PIXEL_MODE_GRAY | PIXEL_MODE_GRAY2 | PIXEL_MODE_GRAY4 => {
+                (bitmap.width, bitmap.rows)
             }
+            PIXEL_MODE_LCD => (bitmap.width / 3, bitmap.rows),
+            PIXEL_MODE_LCD_V => (bitmap.width, bitmap.rows / 3),
+            pixel_mode => return Err(Error::UnsupportedPixelMode(pixel_mode)),
+        };
+        let byte = |row: u32, index: u32| unsafe {
+            *bitmap
+                .buffer
+                .offset(row as isize * bitmap.pitch as isize + index as isize)
+        };
 
-            let data_count_per_pixel = if has_alpha { 4 } else { 3 };
-
-            for y in 0..bitmap.rows {
-                for x in 0..(bitmap.width / data_count_per_pixel) {
-                    let buffer_index =
-                        (y as i32 * bitmap.pitch + x as i32 * data_count_per_pixel as i32) as usize;
-                    let rgba = unsafe {
-                        if has_alpha {
-                            let a = *bitmap.buffer.add(buffer_index + 3);
-                            let div_by_a = |i: u8| ((i as f32) * (255.0 / a as f32)) as u8;
-
-                            (
-                                // BGRA
-                                div_by_a(*bitmap.buffer.add(buffer_index + 2)),
-                                div_by_a(*bitmap.buffer.add(buffer_index + 1)),
-                                div_by_a(*bitmap.buffer.add(buffer_index)),
-                                a,
-                            )
-                        } else {
-                            (
-                                // RGB
-                                *bitmap.buffer.add(buffer_index),
-                                *bitmap.buffer.add(buffer_index + 1),
-                                *bitmap.buffer.add(buffer_index + 2),
-                                255,
-                            )
+        let linear = self.style.blending == Blending::Linear;
+        let to_color = |channel: u8| {
+            if linear {
+                srgb_to_linear(channel)
+            } else {
+                channel as f32 / 255.0
+            }
+        };
+        let foreground = self.style.foreground;
+        let foreground_alpha = foreground.a as f32 / 255.0;
+        let foreground_color = [foreground.r, foreground.g, foreground.b]
+            .map(|channel| to_color(channel) * foreground_alpha);
+
+        for y in 0..rows {
+            for x in 0..width {
+                // Coverage of red, green and blue subpixels
+                let coverage = match bitmap.pixel_mode {
+                    PIXEL_MODE_MONO => {
+                        // Pixels are packed into bits, the most significant bit first
+                        let bit = byte(y, x / 8) & (0x80 >> (x % 8));
+                        let coverage = if bit != 0 { 255 } else { 0 };
+
+                        [coverage; 3]
+                    }
+                    PIXEL_MODE_GRAY => [self.coverage_table[byte(y, x) as usize]; 3],
+                    // Bitmaps of pixel fonts with fewer gray levels
+                    PIXEL_MODE_GRAY2 => {
+                        let level = (byte(y, x / 4) >> (6 - x % 4 * 2)) & 3;
+                        [self.coverage_table[level as usize * 85]; 3]
+                    }
+                    PIXEL_MODE_GRAY4 => {
+                        let level = (byte(y, x / 2) >> (4 - x % 2 * 4)) & 15;
+                        [self.coverage_table[level as usize * 17]; 3]
+                    }
+                    PIXEL_MODE_LCD => [byte(y, x * 3), byte(y, x * 3 + 1), byte(y, x * 3 + 2)]
+                        .map(|coverage| self.coverage_table[coverage as usize]),
+                    _ => [byte(y * 3, x), byte(y * 3 + 1, x), byte(y * 3 + 2, x)]
+                        .map(|coverage| self.coverage_table[coverage as usize]),
+                };
+                if coverage == [0; 3] {
+                    continue;
+                }
+
+                let alpha = coverage.map(|coverage| coverage as f32 / 255.0 * foreground_alpha);
+                let color = [0, 1, 2].map(|i| foreground_color[i] * coverage[i] as f32 / 255.0);
+                result.composite(
+                    origin_x + x as i64 + bitmap_left as i64,
+                    origin_y + y as i64 - bitmap_top as i64,
+                    color,
+                    alpha,
+                    linear,
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draws the rendered color glyph slot, scaled from the size of selected strike
+    ///
+    /// Each pixel averages the premultiplied texels it covers, weighted by covered area.
+    fn draw_color_glyph(&self, result: &mut StringBitmap, origin_x: i64, origin_y: i64) {
+        let (bitmap, bitmap_left, bitmap_top) = unsafe {
+            let glyph = *(*self.raw_ptr).glyph;
+            (glyph.bitmap, glyph.bitmap_left, glyph.bitmap_top)
+        };
+        let scale = self.strike_scale();
+        let width = (bitmap.width as f64 * scale).ceil() as u32;
+        let rows = (bitmap.rows as f64 * scale).ceil() as u32;
+        let left = (bitmap_left as f64 * scale).round() as i64;
+        let top = (bitmap_top as f64 * scale).round() as i64;
+        let texel = |row: u32, column: u32| unsafe {
+            let pixel = bitmap
+                .buffer
+                .offset(row as isize * bitmap.pitch as isize + column as isize * 4);
+            [0, 1, 2, 3].map(|i| *pixel.offset(i) as f32)
+        };
+        // Texels covered by `start..end` of a pixel with their weights
+        let covered = |start: f64, end: f64, count: u32| {
+            let first = start.floor() as u32;
+            let last = std::cmp::min(end.ceil() as u32, count);
+            (first..last).map(move |index| {
+                let weight = end.min(index as f64 + 1.0) - start.max(index as f64);
+                (index, weight as f32)
+            })
+        };
+
+        for y in 0..rows {
+            let (top_edge, bottom_edge) = (y as f64 / scale, (y + 1) as f64 / scale);
+            for x in 0..width {
+                let (left_edge, right_edge) = (x as f64 / scale, (x + 1) as f64 / scale);
+                let mut bgra = [0.0; 4];
+                let mut area = 0.0;
+                for (row, row_weight) in covered(top_edge, bottom_edge, bitmap.rows) {
+                    for (column, column_weight) in covered(left_edge, right_edge, bitmap.width) {
+                        let weight = row_weight * column_weight;
+                        let texel = texel(row, column);
+                        for i in 0..4 {
+                            bgra[i] += texel[i] * weight;
                         }
-                    };
-
-                    let (bitmap_left, bitmap_top) = unsafe {
-                        (
-                            (*(*self.raw_ptr).glyph).bitmap_left,
-                            (*(*self.raw_ptr).glyph).bitmap_top,
-                        )
-                    };
-
-                    result.set_rgba(
-                        pen_x as i64 + x as i64 + bitmap_left as i64,
-                        pen_y as i64
-                            + y as i64
-                            + (size.height as i64 - (bitmap_top as i64 + size.y_min as i64)) as i64,
-                        rgba,
-                    );
+                        area += weight;
+                    }
                 }
+                if area == 0.0 {
+                    continue;
+                }
+
+                // Texels are premultiplied, so are their averages
+                let rgba =
+                    [bgra[2], bgra[1], bgra[0], bgra[3]].map(|channel| channel / area / 255.0);
+                self.composite_color(
+                    result,
+                    origin_x + x as i64 + left,
+                    origin_y + y as i64 - top,
+                    rgba,
+                );
             }
+        }
+    }
 
-            let scale =
-                unsafe { (shape.scale as f64) / (*(*self.raw_ptr).size).metrics.x_ppem as f64 }
-                    * self.letter_spacing;
-            let (x_advance, y_advance) = {
-                (
-                    (shape.x_advance as f64 / scale) as i64,
-                    (shape.y_advance as f64 / scale) as i64,
-                )
-            };
-            pen_x += x_advance;
-            pen_y += y_advance;
+    /// Draws string laid out from top to bottom into bitmap,
+    /// which can be shared with other font-faces
+    ///
+    /// Glyphs are drawn from `pen_y` on `center_x`, which is column index in `result`.
+    /// `pen_y` is in 26.6 fixed-point pixels.
+    /// Returns pen position after the last glyph.
+    pub fn draw_vertical_string(
+        &mut self,
+        shapes: &[Shape],
+        result: &mut StringBitmap,
+        center_x: i64,
+        pen_y: i64,
+    ) -> Result<i64> {
+        // Protect this method as critical section
+        let mutex_cloned = self.render_mutex.clone();
+        let _guard = mutex_cloned.lock();
+
+        self.call_ft_set_chart_size()?;
+        self.draw_vertical_string_without_lock(shapes, result, center_x, pen_y)
+    }
+
+    fn draw_vertical_string_without_lock(
+        &mut self,
+        shapes: &[Shape],
+        result: &mut StringBitmap,
+        center_x: i64,
+        mut pen_y: i64,
+    ) -> Result<i64> {
+        let sideways_baseline = self.sideways_baseline();
+
+        for shape in shapes {
+            // Horizontal origin of glyph
+            match shape.orientation {
+                Orientation::Sideways => {
+                    // Offsets are rotated along with the glyph
+                    let (x_offset, y_offset) = self.offsets(shape);
+                    self.draw_glyph_with_index(
+                        result,
+                        shape.glyph_id,
+                        (center_x << 6) + sideways_baseline + y_offset,
+                        pen_y + x_offset,
+                        true,
+                    )?;
+                }
+                _ => {
+                    // Offsets of vertically shaped glyphs lead from the vertical origin
+                    // on the center line to the horizontal origin
+                    let (x_offset, y_offset) = self.offsets(shape);
+                    self.draw_glyph_with_index(
+                        result,
+                        shape.glyph_id,
+                        (center_x << 6) + x_offset,
+                        pen_y - y_offset,
+                        false,
+                    )?;
+                }
+            }
+            pen_y += self.vertical_advance(shape);
         }
 
-        Ok(result)
+        Ok(pen_y)
     }
 
     pub fn set_letter_spacing(&mut self, letter_spacing: f64) {
         self.letter_spacing = letter_spacing;
     }
+
+    /// Sets how glyphs are rasterized
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.render_mode = render_mode;
+    }
+
+    /// Sets colors of rendered text
+    pub fn set_style(&mut self, style: TextStyle) {
+        if style.foreground != self.style.foreground {
+            self.painter = None;
+        }
+        self.style = style;
+        self.coverage_table = style.blending.coverage_table(style.foreground);
+    }
+
+    pub fn style(&self) -> &TextStyle {
+        &self.style
+    }
+
+    /// Sets number of fractional positions a glyph can be rendered at within a pixel
+    ///
+    /// `1` snaps every glyph to whole pixels, and values are clamped to `1..=64`.
+    pub fn set_subpixel_bins(&mut self, bins: u8) {
+        self.subpixel_bins = bins.clamp(1, 64);
+    }
+
+    /// Sets how outlines are fitted to the pixel grid
+    pub fn set_hinting(&mut self, hinting: Hinting) {
+        self.hinting = hinting;
+    }
+
+    /// Sets whether embedded bitmaps are preferred to outlines
+    ///
+    /// Strikes matching the size are used instead of outlines,
+    /// and color bitmap fonts are scaled from the best strike to the requested size.
+    /// Bitmaps are not transformed, so sideways glyphs of vertical text are not rotated.
+    pub fn set_embedded_bitmaps(&mut self, embedded_bitmaps: bool) {
+        self.embedded_bitmaps = embedded_bitmaps;
+    }
+
+    /// Sets index of palette which color glyphs are painted with
+    ///
+    /// The first palette is used when the font has no palette of the index.
+    pub fn set_palette(&mut self, palette_index: u16) {
+        if palette_index != self.palette {
+            self.painter = None;
+        }
+        self.palette = palette_index;
+    }
+
+    /// Sets design coordinates of variation axes, in order of axes in `fvar` table
+    ///
+    /// Axes without coordinates are set to their defaults.
+    pub fn set_variations(&mut self, coordinates: &[f32]) {
+        self.variation_coordinates = coordinates
+            .iter()
+            .map(|coordinate| (coordinate * 65536.0).round() as FT_Fixed)
+            .collect();
+    }
+
+    /// Sets metrics which `MVAR` table varies, with `metric` giving the position of a tag in font units
+    ///
+    /// It must be called with the same variations as `set_variations`.
+    pub fn set_varied_metrics(&mut self, metric: impl Fn([u8; 4]) -> Option<i32>) {
+        self.varied_metrics = VARIED_METRICS
+            .iter()
+            .filter_map(|tag| Some((*tag, metric(*tag)?.into())))
+            .collect();
+    }
+
+    /// Style of the face from `OS/2` and `post` tables
+    ///
+    /// Names are the family and style names of FreeType, and scripts are left empty.
+    pub fn info(&self) -> FontInfo {
+        let (italic, bold) = unsafe {
+            let style_flags = (*self.raw_ptr).style_flags as u32;
+            (
+                style_flags & FT_STYLE_FLAG_ITALIC != 0,
+                style_flags & FT_STYLE_FLAG_BOLD != 0,
+            )
+        };
+        let mut info = FontInfo {
+            names: self.freetype_names(),
+            weight_class: if bold { 700 } else { 400 },
+            width_class: 5,
+            fs_selection: match (italic, bold) {
+                (false, false) => 1 << 6,
+                (italic, bold) => italic as u16 | (bold as u16) << 5,
+            },
+            panose: [0; 10],
+            italic_angle: 0.0,
+            fixed_pitch: self.has_face_flag(FT_FACE_FLAG_FIXED_WIDTH),
+            scripts: Vec::new(),
+        };
+
+        unsafe {
+            if let Some(os2) = self.os2() {
+                info.weight_class = os2.usWeightClass;
+                info.width_class = os2.usWidthClass;
+                info.fs_selection = os2.fsSelection;
+                info.panose = os2.panose;
+            }
+
+            let post =
+                FT_Get_Sfnt_Table(self.raw_ptr, FT_Sfnt_Tag::FT_SFNT_POST) as *const TT_Postscript;
+            if !post.is_null() {
+                info.italic_angle = (*post).italic_angle as f32 / 65536.0;
+            }
+        }
+
+        info
+    }
+
+    /// Glyph ID which the selected charmap maps `c` to, `None` for missing glyph
+    pub fn glyph_index(&self, c: char) -> Option<u32> {
+        // Protect this method as critical section
+        let mutex_cloned = self.render_mutex.clone();
+        let _guard = mutex_cloned.lock();
+
+        let glyph_index = unsafe { FT_Get_Char_Index(self.raw_ptr, c as _) };
+
+        (glyph_index != 0).then_some(glyph_index)
+    }
+
+    /// Ranges of characters mapped to glyphs by the selected charmap, which is Unicode if any
+    pub fn char_ranges(&self) -> Vec<Range<u32>> {
+        // Protect this method as critical section
+        let mutex_cloned = self.render_mutex.clone();
+        let _guard = mutex_cloned.lock();
+
+        let mut ranges: Vec<Range<u32>> = Vec::new();
+        unsafe {
+            let mut glyph_index = 0;
+            let mut char_code = FT_Get_First_Char(self.raw_ptr, &mut glyph_index);
+            while glyph_index != 0 {
+                let code = char_code as u32;
+                match ranges.last_mut() {
+                    Some(range) if range.end == code => range.end += 1,
+                    _ => ranges.push(code..code + 1),
+                }
+                char_code = FT_Get_Next_Char(self.raw_ptr, char_code, &mut glyph_index);
+            }
+        }
+
+        ranges
+    }
+
+    /// Family and style names known to FreeType, in English
+    fn freetype_names(&self) -> Vec<NameEntry> {
+        let (family, style) = unsafe { ((*self.raw_ptr).family_name, (*self.raw_ptr).style_name) };
+
+        [(NameId::Family, family), (NameId::Subfamily, style)]
+            .into_iter()
+            .filter(|(_, name)| !name.is_null())
+            .map(|(id, name)| NameEntry {
+                id,
+                language: "en".to_string(),
+                value: unsafe { std::ffi::CStr::from_ptr(name) }
+                    .to_string_lossy()
+                    .into_owned(),
+            })
+            .collect()
+    }
+
+    /// Flags for `FT_Load_Glyph`, which HarfBuzz uses as well for hinted advances
+    pub(crate) fn load_flags(&self) -> i32 {
+        let hinting = match self.hinting {
+            Hinting::None => FT_LOAD_NO_HINTING,
+            Hinting::Light => load_target(FT_Render_Mode::FT_RENDER_MODE_LIGHT),
+            Hinting::Normal => load_target(FT_Render_Mode::FT_RENDER_MODE_NORMAL),
+            Hinting::Mono => load_target(FT_Render_Mode::FT_RENDER_MODE_MONO),
+            Hinting::Auto => FT_LOAD_FORCE_AUTOHINT,
+        };
+
+        // Bitmap-only fonts have nothing but bitmaps to load
+        let bitmaps = if self.embedded_bitmaps || !self.has_face_flag(FT_FACE_FLAG_SCALABLE) {
+            FT_LOAD_COLOR
+        } else {
+            FT_LOAD_NO_BITMAP
+        };
+
+        (bitmaps | hinting) as i32
+    }
+
+    /// Whether HarfBuzz should take advances from hinted outlines
+    pub(crate) fn is_hinted(&self) -> bool {
+        self.hinting != Hinting::None
+    }
+
+    /// Runs `f` while face is locked and sized, for HarfBuzz reading the same `FT_Face`
+    pub(crate) fn with_face<R>(&self, f: impl FnOnce() -> R) -> Result<R> {
+        // Protect this method as critical section
+        let mutex_cloned = self.render_mutex.clone();
+        let _guard = mutex_cloned.lock();
+
+        self.call_ft_set_chart_size()?;
+        Ok(f())
+    }
+
+    pub(crate) fn raw_ptr(&self) -> FT_Face {
+        self.raw_ptr
+    }
+}
+
+/// Opens face of `face_index` in `data`, which must outlive the face
+fn new_memory_face(data: &[u8], face_index: i64) -> Result<FT_Face> {
+    let library = match init_freetype() {
+        Ok(ptr_wrapper) => ptr_wrapper.ptr,
+        Err(err) => return Err(err.clone()),
+    };
+
+    let mut raw_face_ptr = std::ptr::null_mut();
+    let err = unsafe {
+        FT_New_Memory_Face(
+            library,
+            data.as_ptr(),
+            data.len() as i64,
+            face_index,
+            &mut raw_face_ptr,
+        )
+    };
+
+    error_if_not_zero!(err, raw_face_ptr)
+}
+
+/// `FT_LOAD_TARGET_XXX` macro of FreeType
+fn load_target(mode: FT_Render_Mode) -> u32 {
+    (mode as u32 & 15) << 16
+}
+
+fn raw_render_mode(render_mode: RenderMode) -> Result<FT_Render_Mode> {
+    match render_mode {
+        RenderMode::Normal => Ok(FT_Render_Mode::FT_RENDER_MODE_NORMAL),
+        RenderMode::Mono => Ok(FT_Render_Mode::FT_RENDER_MODE_MONO),
+        RenderMode::Lcd => Ok(FT_Render_Mode::FT_RENDER_MODE_LCD),
+        RenderMode::LcdV => Ok(FT_Render_Mode::FT_RENDER_MODE_LCD_V),
+        // Older FreeType takes the value as the end of render modes
+        RenderMode::Sdf if library_version() < (2, 11, 0) => {
+            Err(Error::UnsupportedRenderMode(render_mode))
+        }
+        RenderMode::Sdf => Ok(FT_RENDER_MODE_SDF),
+    }
+}
+
+/// Writes distances of rendered signed distance field into every channel of `result`
+///
+/// Distances are not blended with style, and overlapping glyphs keep the larger distance
+/// so that the field stays the union of their outlines.
+fn draw_distance_field(result: &mut StringBitmap, bitmap: &FT_Bitmap, left: i64, top: i64) {
+    let size = result.size;
+    for y in 0..bitmap.rows {
+        for x in 0..bitmap.width {
+            let (x_pos, y_pos) = (left + x as i64, top + y as i64);
+            if x_pos < 0 || y_pos < 0 || x_pos >= size.width as i64 || y_pos >= size.height as i64 {
+                continue;
+            }
+            let distance = unsafe {
+                *bitmap
+                    .buffer
+                    .offset(y as isize * bitmap.pitch as isize + x as isize)
+            };
+            let pos = result.get_pos(x_pos, y_pos);
+            for channel in [&mut result.r, &mut result.g, &mut result.b, &mut result.a] {
+                channel[pos] = channel[pos].max(distance);
+            }
+        }
+    }
+}
+
+/// Whether shapes are laid out in vertical text
+fn is_vertical(shapes: &[Shape]) -> bool {
+    shapes
+        .iter()
+        .any(|shape| shape.orientation != Orientation::Horizontal)
 }
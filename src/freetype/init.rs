@@ -1,6 +1,8 @@
 use std::sync::OnceLock;
 
-use freetype::freetype::{FT_Init_FreeType, FT_Library};
+use freetype::freetype::{FT_Init_FreeType, FT_Library, FT_Library_Version};
+
+use crate::error::{Error, FreeTypeError};
 
 /// Wrapper of FT_Library to bypass rust compiler errors
 #[derive(Clone, Copy)]
@@ -16,8 +18,8 @@ unsafe impl Sync for FreeTypeLibraryPointerWrapper {}
 ///
 /// FreeType library is initialized only one time even when
 /// `init_freetype` method function is called multiple times
-pub(super) fn init_freetype() -> &'static Result<FreeTypeLibraryPointerWrapper, i32> {
-    static RAW_LIBRARY_PTR_INIT: OnceLock<Result<FreeTypeLibraryPointerWrapper, i32>> =
+pub(super) fn init_freetype() -> &'static Result<FreeTypeLibraryPointerWrapper, Error> {
+    static RAW_LIBRARY_PTR_INIT: OnceLock<Result<FreeTypeLibraryPointerWrapper, Error>> =
         OnceLock::new();
     unsafe {
         RAW_LIBRARY_PTR_INIT.get_or_init(|| {
@@ -25,7 +27,7 @@ pub(super) fn init_freetype() -> &'static Result<FreeTypeLibraryPointerWrapper,
             let err = FT_Init_FreeType(&mut raw_library_ptr);
 
             if err != 0 {
-                Err(err)
+                Err(FreeTypeError::new(err).into())
             } else {
                 Ok(FreeTypeLibraryPointerWrapper {
                     ptr: raw_library_ptr,
@@ -34,3 +36,21 @@ pub(super) fn init_freetype() -> &'static Result<FreeTypeLibraryPointerWrapper,
         })
     }
 }
+
+/// Version of the FreeType library loaded at runtime as (major, minor, patch)
+///
+/// Render modes and layout of some structures depend on it,
+/// and it may be newer or older than the headers the bindings come from.
+pub(super) fn library_version() -> (i32, i32, i32) {
+    static VERSION: OnceLock<(i32, i32, i32)> = OnceLock::new();
+    *VERSION.get_or_init(|| match init_freetype() {
+        Ok(library) => {
+            let (mut major, mut minor, mut patch) = (0, 0, 0);
+            unsafe {
+                FT_Library_Version(library.ptr, &mut major, &mut minor, &mut patch);
+            }
+            (major, minor, patch)
+        }
+        Err(_) => (0, 0, 0),
+    })
+}
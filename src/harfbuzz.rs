@@ -1,3 +1,5 @@
 pub(crate) mod buffer;
+pub(crate) mod feature;
 pub(crate) mod font;
 pub(crate) mod shape;
+pub(crate) mod unicode;
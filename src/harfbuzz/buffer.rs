@@ -1,8 +1,18 @@
-use std::ffi::CString;
+use std::{ffi::CString, ops::Range};
 
 use harfbuzz_sys::{
-    hb_buffer_add_utf8, hb_buffer_create, hb_buffer_destroy, hb_buffer_guess_segment_properties,
-    hb_buffer_t,
+    hb_buffer_add_utf8, hb_buffer_allocation_successful, hb_buffer_cluster_level_t,
+    hb_buffer_create, hb_buffer_destroy, hb_buffer_guess_segment_properties,
+    hb_buffer_set_cluster_level, hb_buffer_set_direction, hb_buffer_set_language,
+    hb_buffer_set_script, hb_buffer_t, hb_direction_t, hb_language_from_string,
+    hb_script_from_string, HB_BUFFER_CLUSTER_LEVEL_CHARACTERS,
+    HB_BUFFER_CLUSTER_LEVEL_MONOTONE_CHARACTERS, HB_BUFFER_CLUSTER_LEVEL_MONOTONE_GRAPHEMES,
+    HB_DIRECTION_BTT, HB_DIRECTION_LTR, HB_DIRECTION_RTL, HB_DIRECTION_TTB,
+};
+
+use crate::{
+    error::{Error, Result},
+    shaping::{ClusterLevel, Direction, ShapingOptions},
 };
 
 pub struct Buffer {
@@ -17,18 +27,77 @@ impl Drop for Buffer {
     }
 }
 
+fn raw_direction(direction: Direction) -> hb_direction_t {
+    match direction {
+        Direction::LeftToRight => HB_DIRECTION_LTR,
+        Direction::RightToLeft => HB_DIRECTION_RTL,
+        Direction::TopToBottom => HB_DIRECTION_TTB,
+        Direction::BottomToTop => HB_DIRECTION_BTT,
+    }
+}
+
+fn raw_cluster_level(cluster_level: ClusterLevel) -> hb_buffer_cluster_level_t {
+    match cluster_level {
+        ClusterLevel::MonotoneGraphemes => HB_BUFFER_CLUSTER_LEVEL_MONOTONE_GRAPHEMES,
+        ClusterLevel::MonotoneCharacters => HB_BUFFER_CLUSTER_LEVEL_MONOTONE_CHARACTERS,
+        ClusterLevel::Characters => HB_BUFFER_CLUSTER_LEVEL_CHARACTERS,
+    }
+}
+
 impl Buffer {
-    pub fn new(str: &str) -> Buffer {
+    /// Creates buffer with `range` of `str` as the item to shape
+    ///
+    /// The rest of `str` is added as pre/post-context,
+    /// so that shaping across run boundaries (e.g. Arabic joining) stays correct.
+    /// Cluster values are byte offsets into `str`.
+    ///
+    /// Segment properties given in `options` are applied first,
+    /// then the rest of them are guessed by HarfBuzz.
+    pub fn new(str: &str, range: Range<usize>, options: &ShapingOptions) -> Result<Buffer> {
+        let c_str = CString::new(str).map_err(Error::InvalidText)?;
+        if let Some(script) = &options.script {
+            if script.len() != 4 || !script.bytes().all(|c| c.is_ascii_alphabetic()) {
+                return Err(Error::InvalidScript(script.clone()));
+            }
+        }
+
         let buf = unsafe {
             let buf = hb_buffer_create();
             let str_len = str.len();
-            let c_str = CString::new(str).unwrap();
-            hb_buffer_add_utf8(buf, c_str.as_ptr(), str_len as i32, 0, str_len as i32);
+            hb_buffer_add_utf8(
+                buf,
+                c_str.as_ptr(),
+                str_len as i32,
+                range.start as u32,
+                range.len() as i32,
+            );
+            // Failed buffer is the inert empty buffer, which is safe to destroy
+            if hb_buffer_allocation_successful(buf) == 0 {
+                hb_buffer_destroy(buf);
+                return Err(Error::HarfBuzzBuffer);
+            }
+
+            if let Some(script) = &options.script {
+                hb_buffer_set_script(
+                    buf,
+                    hb_script_from_string(script.as_ptr() as *const _, script.len() as i32),
+                );
+            }
+            if let Some(language) = &options.language {
+                hb_buffer_set_language(
+                    buf,
+                    hb_language_from_string(language.as_ptr() as *const _, language.len() as i32),
+                );
+            }
+            if let Some(direction) = options.direction {
+                hb_buffer_set_direction(buf, raw_direction(direction));
+            }
+            hb_buffer_set_cluster_level(buf, raw_cluster_level(options.cluster_level));
             hb_buffer_guess_segment_properties(buf);
 
             buf
         };
 
-        return Buffer { raw_ptr: buf };
+        Ok(Buffer { raw_ptr: buf })
     }
 }
@@ -0,0 +1,39 @@
+use harfbuzz_sys::{hb_feature_from_string, hb_feature_t};
+
+use crate::shaping::Feature;
+
+/// Parses feature string with `hb_feature_from_string`
+pub fn parse(str: &str) -> Option<Feature> {
+    let mut raw = hb_feature_t {
+        tag: 0,
+        value: 0,
+        start: 0,
+        end: 0,
+    };
+    let ok =
+        unsafe { hb_feature_from_string(str.as_ptr() as *const _, str.len() as i32, &mut raw) };
+
+    if ok == 0 {
+        None
+    } else {
+        Some(Feature {
+            tag: raw.tag.to_be_bytes(),
+            value: raw.value,
+            start: raw.start,
+            end: raw.end,
+        })
+    }
+}
+
+/// Converts features into array which can be passed to `hb_shape`
+pub fn to_raw(features: &[Feature]) -> Vec<hb_feature_t> {
+    features
+        .iter()
+        .map(|feature| hb_feature_t {
+            tag: u32::from_be_bytes(feature.tag),
+            value: feature.value,
+            start: feature.start,
+            end: feature.end,
+        })
+        .collect()
+}
@@ -1,22 +1,54 @@
 use std::{
-    ffi::CString,
+    ffi::{c_int, c_void},
     sync::{
         atomic::{AtomicU8, Ordering},
         Arc, Mutex,
     },
 };
 
+use harfbuzz_sys::freetype::hb_ft_font_create_referenced;
 use harfbuzz_sys::{
-    hb_blob_create_from_file, hb_blob_destroy, hb_blob_t, hb_face_create, hb_face_destroy,
-    hb_face_get_upem, hb_face_t, hb_font_create, hb_font_destroy, hb_font_t,
+    hb_blob_create, hb_blob_destroy, hb_blob_get_length, hb_blob_t, hb_face_create,
+    hb_face_destroy, hb_face_get_glyph_count, hb_face_t, hb_font_create, hb_font_destroy,
+    hb_font_get_scale, hb_font_set_scale, hb_font_set_var_coords_design, hb_font_t, hb_language_t,
+    hb_language_to_string, hb_ot_layout_script_get_language_tags,
+    hb_ot_layout_table_get_script_tags, hb_ot_metrics_get_position, hb_ot_name_get_utf8,
+    hb_ot_name_id_t, hb_ot_name_list_names, hb_ot_var_axis_info_t, hb_ot_var_get_axis_count,
+    hb_ot_var_get_axis_infos, hb_ot_var_get_named_instance_count,
+    hb_ot_var_named_instance_get_design_coords, hb_ot_var_named_instance_get_subfamily_name_id,
+    HB_MEMORY_MODE_READONLY, HB_OT_VAR_AXIS_FLAG_HIDDEN,
 };
 
+use freetype::freetype::FT_Face;
+
+use crate::{
+    error::{Error, Result},
+    info::{LayoutScript, NameEntry, NameId},
+    variation::{NamedInstance, VariationAxis},
+};
+
+extern "C" {
+    fn hb_ft_font_set_load_flags(font: *mut hb_font_t, load_flags: c_int);
+    fn hb_ft_font_changed(font: *mut hb_font_t);
+    fn hb_ft_font_get_face(font: *mut hb_font_t) -> FT_Face;
+}
+
 pub struct Font {
     pub(super) blob_ptr: *mut hb_blob_t,
     pub(super) face_ptr: *mut hb_face_t,
     pub(super) font_ptr: *mut hb_font_t,
+    /// Font with FreeType font funcs, which gives hinted advances
+    ///
+    /// It reads the `FT_Face` of `freetype::face::FontFace`,
+    /// so it must be used while the face is locked and sized.
+    pub(super) freetype_font_ptr: *mut hb_font_t,
+    /// Whether HarfBuzz cannot read glyphs from font data, as in bitmap-only fonts
+    ///
+    /// Such fonts are shaped with FreeType font funcs only.
+    freetype_only: bool,
     pub(super) ppem: (u16, u16),
-    pub(super) upem: u32,
+    /// Design coordinates of variation axes, empty for the default instance
+    coordinates: Vec<f32>,
 
     counter: Arc<AtomicU8>,
     pub(super) lock: Arc<Mutex<bool>>,
@@ -27,12 +59,14 @@ impl Clone for Font {
         self.counter.fetch_add(1, Ordering::Relaxed);
 
         Self {
-            blob_ptr: self.blob_ptr.clone(),
-            face_ptr: self.face_ptr.clone(),
-            font_ptr: self.font_ptr.clone(),
-            upem: self.upem.clone(),
+            blob_ptr: self.blob_ptr,
+            face_ptr: self.face_ptr,
+            font_ptr: self.font_ptr,
+            freetype_font_ptr: self.freetype_font_ptr,
+            freetype_only: self.freetype_only,
             counter: self.counter.clone(),
-            ppem: self.ppem.clone(),
+            ppem: self.ppem,
+            coordinates: self.coordinates.clone(),
             lock: self.lock.clone(),
         }
     }
@@ -44,6 +78,7 @@ impl Drop for Font {
 
         if self.counter.load(Ordering::Relaxed) == 0 {
             unsafe {
+                hb_font_destroy(self.freetype_font_ptr);
                 hb_font_destroy(self.font_ptr);
                 hb_face_destroy(self.face_ptr);
                 hb_blob_destroy(self.blob_ptr);
@@ -52,30 +87,292 @@ impl Drop for Font {
     }
 }
 
+/// Releases font data referenced by HarfBuzz blob
+unsafe extern "C" fn destroy_blob_data(user_data: *mut c_void) {
+    drop(Box::from_raw(user_data as *mut Arc<[u8]>));
+}
+
 impl Font {
-    pub fn new(path: &str, index: u32) -> Font {
-        let (blob_ptr, face_ptr, font_ptr, upem) = unsafe {
-            let c_str = CString::new(path).unwrap();
-            let blob = hb_blob_create_from_file(c_str.as_ptr());
+    /// Creates font from font data in memory, and `freetype_face` loaded from the same data
+    ///
+    /// The blob holds its own reference to `data`,
+    /// so memory stays alive until HarfBuzz destroys the blob.
+    /// HarfBuzz holds its own reference to `freetype_face` as well.
+    pub fn from_bytes(data: &Arc<[u8]>, index: u32, freetype_face: FT_Face) -> Result<Font> {
+        let (blob_ptr, face_ptr, font_ptr, freetype_font_ptr, freetype_only) = unsafe {
+            let user_data = Box::into_raw(Box::new(data.clone()));
+            // HarfBuzz returns empty blob instead of null when blob cannot be created
+            let blob = hb_blob_create(
+                data.as_ptr() as *const _,
+                data.len() as u32,
+                HB_MEMORY_MODE_READONLY,
+                user_data as *mut c_void,
+                Some(destroy_blob_data),
+            );
+            if blob.is_null() || hb_blob_get_length(blob) == 0 {
+                hb_blob_destroy(blob);
+                return Err(Error::HarfBuzzBlob);
+            }
+
+            // Same goes for face, which has no glyphs unless font data is OpenType.
+            // FreeType has already checked the index, so it must be a font of other formats.
             let face = hb_face_create(blob, index);
+            if face.is_null() {
+                hb_face_destroy(face);
+                hb_blob_destroy(blob);
+                return Err(Error::HarfBuzzFace);
+            }
+
             let font = hb_font_create(face);
-            let upem = hb_face_get_upem(face);
+            let freetype_font = hb_ft_font_create_referenced(freetype_face.cast());
 
-            (blob, face, font, upem)
+            (
+                blob,
+                face,
+                font,
+                freetype_font,
+                hb_face_get_glyph_count(face) == 0,
+            )
         };
 
-        Font {
-            blob_ptr: blob_ptr,
-            face_ptr: face_ptr,
-            font_ptr: font_ptr,
-            upem: upem,
+        Ok(Font {
+            blob_ptr,
+            face_ptr,
+            font_ptr,
+            freetype_font_ptr,
+            freetype_only,
             ppem: (64, 64),
+            coordinates: Vec::new(),
             counter: Arc::new(AtomicU8::new(1)),
             lock: Arc::new(Mutex::new(false)),
-        }
+        })
     }
 
     pub fn set_ppem(&mut self, x_ppem: u16, y_ppem: u16) {
         self.ppem = (x_ppem, y_ppem);
     }
+
+    /// Sets design coordinates of variation axes, in order of `axes`
+    ///
+    /// Fonts with FreeType font funcs take coordinates from the face instead.
+    pub fn set_variations(&mut self, coordinates: Vec<f32>) {
+        self.coordinates = coordinates;
+    }
+
+    /// Position of OpenType metric `tag` such as `hasc` in font units with variations applied,
+    /// or `None` if the font has no table for it
+    pub fn metric(&self, tag: [u8; 4]) -> Option<i32> {
+        let _guard = self.lock.lock();
+        let font_ptr = self.raw_ptr(None);
+        let mut position = 0;
+        let found =
+            unsafe { hb_ot_metrics_get_position(font_ptr, u32::from_be_bytes(tag), &mut position) };
+
+        (found != 0).then_some(position)
+    }
+
+    /// Variation axes of the font, empty unless it is a variable font
+    pub fn axes(&self) -> Vec<VariationAxis> {
+        let count = unsafe { hb_ot_var_get_axis_count(self.face_ptr) };
+        let mut infos = Vec::with_capacity(count as usize);
+        let mut length = count;
+        unsafe {
+            hb_ot_var_get_axis_infos(self.face_ptr, 0, &mut length, infos.as_mut_ptr());
+            infos.set_len(length as usize);
+        }
+
+        infos
+            .iter()
+            .map(|info: &hb_ot_var_axis_info_t| VariationAxis {
+                tag: info.tag.to_be_bytes(),
+                name: self.name(info.name_id),
+                min: info.min_value,
+                default: info.default_value,
+                max: info.max_value,
+                hidden: info.flags & HB_OT_VAR_AXIS_FLAG_HIDDEN != 0,
+            })
+            .collect()
+    }
+
+    /// Named instances of the font, empty unless it is a variable font
+    pub fn named_instances(&self) -> Vec<NamedInstance> {
+        let axis_count = unsafe { hb_ot_var_get_axis_count(self.face_ptr) };
+        let count = unsafe { hb_ot_var_get_named_instance_count(self.face_ptr) };
+
+        (0..count)
+            .map(|index| unsafe {
+                let mut coordinates = vec![0.0; axis_count as usize];
+                let mut length = axis_count;
+                hb_ot_var_named_instance_get_design_coords(
+                    self.face_ptr,
+                    index,
+                    &mut length,
+                    coordinates.as_mut_ptr(),
+                );
+                coordinates.truncate(length as usize);
+                let name_id = hb_ot_var_named_instance_get_subfamily_name_id(self.face_ptr, index);
+
+                NamedInstance {
+                    name: self.name(name_id),
+                    coordinates,
+                }
+            })
+            .collect()
+    }
+
+    /// Entries of `name` table for every `NameId` in every language
+    pub fn names(&self) -> Vec<NameEntry> {
+        let entries = unsafe {
+            let mut count = 0;
+            let entries = hb_ot_name_list_names(self.face_ptr, &mut count);
+            if entries.is_null() {
+                return Vec::new();
+            }
+
+            std::slice::from_raw_parts(entries, count as usize)
+        };
+
+        entries
+            .iter()
+            .filter_map(|entry| {
+                let id = NameId::from_raw(entry.name_id as u16)?;
+                let language = unsafe {
+                    std::ffi::CStr::from_ptr(hb_language_to_string(entry.language))
+                        .to_string_lossy()
+                        .into_owned()
+                };
+
+                Some(NameEntry {
+                    id,
+                    language,
+                    value: self.name_in(entry.name_id, entry.language),
+                })
+            })
+            .collect()
+    }
+
+    /// Scripts of `GSUB` and `GPOS` tables, merged and sorted by tag
+    pub fn layout_scripts(&self) -> Vec<LayoutScript> {
+        let mut scripts: Vec<LayoutScript> = Vec::new();
+        for table in [*b"GSUB", *b"GPOS"] {
+            let table = u32::from_be_bytes(table);
+            let tags = read_tags(|start, count, tags| unsafe {
+                hb_ot_layout_table_get_script_tags(self.face_ptr, table, start, count, tags)
+            });
+
+            for (script_index, tag) in tags.into_iter().enumerate() {
+                let languages = read_tags(|start, count, tags| unsafe {
+                    hb_ot_layout_script_get_language_tags(
+                        self.face_ptr,
+                        table,
+                        script_index as u32,
+                        start,
+                        count,
+                        tags,
+                    )
+                });
+                let tag = tag.to_be_bytes();
+                let languages = languages.into_iter().map(u32::to_be_bytes);
+
+                match scripts.iter_mut().find(|script| script.tag == tag) {
+                    Some(script) => script.languages.extend(languages),
+                    None => scripts.push(LayoutScript {
+                        tag,
+                        languages: languages.collect(),
+                    }),
+                }
+            }
+        }
+
+        for script in &mut scripts {
+            script.languages.sort();
+            script.languages.dedup();
+        }
+        scripts.sort_by_key(|script| script.tag);
+
+        scripts
+    }
+
+    /// Entry of `name` table in the default language, empty if missing
+    fn name(&self, name_id: hb_ot_name_id_t) -> String {
+        self.name_in(name_id, std::ptr::null())
+    }
+
+    /// Entry of `name` table in `language`, empty if missing
+    fn name_in(&self, name_id: hb_ot_name_id_t, language: hb_language_t) -> String {
+        unsafe {
+            let length = hb_ot_name_get_utf8(
+                self.face_ptr,
+                name_id,
+                language,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            );
+            // Room for the terminating NUL, which HarfBuzz always writes
+            let mut size = length + 1;
+            let mut buffer = vec![0u8; size as usize];
+            hb_ot_name_get_utf8(
+                self.face_ptr,
+                name_id,
+                language,
+                &mut size,
+                buffer.as_mut_ptr() as *mut _,
+            );
+            buffer.truncate(size as usize);
+
+            String::from_utf8_lossy(&buffer).into_owned()
+        }
+    }
+
+    /// Whether shaping needs FreeType font funcs, given by `raw_ptr` with load flags
+    pub(crate) fn needs_freetype(&self) -> bool {
+        self.freetype_only
+    }
+
+    /// Font pointer used for shaping
+    ///
+    /// With `load_flags`, the font with FreeType font funcs is synced with size and variations
+    /// of the face and loads glyphs with the flags. Otherwise, unhinted advances of OpenType font funcs are used.
+    pub(super) fn raw_ptr(&self, load_flags: Option<i32>) -> *mut hb_font_t {
+        match load_flags {
+            Some(load_flags) => unsafe {
+                hb_ft_font_changed(self.freetype_font_ptr);
+                hb_ft_font_set_load_flags(self.freetype_font_ptr, load_flags);
+
+                // Scale is zero for faces without design units, whose advances are in 26.6 pixels
+                let (mut x_scale, mut y_scale) = (0, 0);
+                hb_font_get_scale(self.freetype_font_ptr, &mut x_scale, &mut y_scale);
+                if x_scale == 0 || y_scale == 0 {
+                    let metrics = (*(*hb_ft_font_get_face(self.freetype_font_ptr)).size).metrics;
+                    hb_font_set_scale(
+                        self.freetype_font_ptr,
+                        metrics.x_ppem as i32 * 64,
+                        metrics.y_ppem as i32 * 64,
+                    );
+                }
+
+                self.freetype_font_ptr
+            },
+            None => unsafe {
+                hb_font_set_var_coords_design(
+                    self.font_ptr,
+                    self.coordinates.as_ptr(),
+                    self.coordinates.len() as u32,
+                );
+
+                self.font_ptr
+            },
+        }
+    }
+}
+
+/// Reads every tag from HarfBuzz getter taking start offset, count and array of tags
+fn read_tags(get: impl Fn(u32, *mut u32, *mut u32) -> u32) -> Vec<u32> {
+    let total = get(0, &mut 0, std::ptr::null_mut());
+    let mut tags = vec![0; total as usize];
+    let mut count = total;
+    get(0, &mut count, tags.as_mut_ptr());
+    tags.truncate(count as usize);
+
+    tags
 }
@@ -1,26 +1,44 @@
 use harfbuzz_sys::{
-    hb_buffer_get_glyph_infos, hb_buffer_get_glyph_positions, hb_glyph_info_t, hb_glyph_position_t,
-    hb_shape,
+    hb_buffer_get_glyph_infos, hb_buffer_get_glyph_positions, hb_font_get_scale, hb_glyph_info_t,
+    hb_glyph_position_t, hb_shape,
 };
 
-use super::{buffer::Buffer, font::Font};
+use crate::shaping::Feature;
+
+use super::{buffer::Buffer, feature, font::Font};
 
 struct Shaper {
     glyph_count: u32,
     glyph_index: u32,
     glyph_info_ptr: *mut hb_glyph_info_t,
     glyph_position_ptr: *mut hb_glyph_position_t,
-    scale: u32,
+    scale: (u32, u32),
+    orientation: Orientation,
+}
+
+/// How glyph is placed in line of text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    /// Horizontal line
+    Horizontal,
+    /// Vertical line, glyph shaped vertically and standing upright
+    Upright,
+    /// Vertical line, glyph shaped horizontally and rotated 90 degrees clockwise
+    Sideways,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Shape {
     pub glyph_id: u32,
+    /// Byte offset of the first character this glyph comes from
+    pub cluster: u32,
     pub x_offset: i32,
     pub y_offset: i32,
     pub x_advance: i32,
     pub y_advance: i32,
-    pub scale: u32,
+    /// Scale of HarfBuzz font, which is size of em in the units of advances and offsets
+    pub scale: (u32, u32),
+    pub orientation: Orientation,
 }
 
 impl Iterator for Shaper {
@@ -31,7 +49,11 @@ impl Iterator for Shaper {
             return None;
         }
 
-        let glyph_id = unsafe { (*self.glyph_info_ptr.add(self.glyph_index as usize)).codepoint };
+        let (glyph_id, cluster) = unsafe {
+            let info = *self.glyph_info_ptr.add(self.glyph_index as usize);
+
+            (info.codepoint, info.cluster)
+        };
         let (x_offset, y_offset, x_advance, y_advance) = unsafe {
             let position = *self.glyph_position_ptr.add(self.glyph_index as usize);
 
@@ -45,26 +67,51 @@ impl Iterator for Shaper {
 
         self.glyph_index += 1;
         Some(Shape {
-            glyph_id: glyph_id,
-            x_offset: x_offset,
-            y_offset: y_offset,
-            x_advance: x_advance,
-            y_advance: y_advance,
+            glyph_id,
+            cluster,
+            x_offset,
+            y_offset,
+            x_advance,
+            y_advance,
             scale: self.scale,
+            orientation: self.orientation,
         })
     }
 }
 
-pub fn shape(buffer: Buffer, font: &Font) -> Vec<Shape> {
+/// Shapes buffer, with FreeType font funcs loading glyphs with `load_flags` if given
+pub fn shape(
+    buffer: Buffer,
+    font: &Font,
+    features: &[Feature],
+    orientation: Orientation,
+    load_flags: Option<i32>,
+) -> Vec<Shape> {
+    let raw_features = feature::to_raw(features);
     let _guard = font.lock.lock();
-    let (count, info_ptr, pos_ptr) = unsafe {
-        hb_shape(font.font_ptr, buffer.raw_ptr, std::ptr::null(), 0);
+    let font_ptr = font.raw_ptr(load_flags);
+    let (count, info_ptr, pos_ptr, scale) = unsafe {
+        hb_shape(
+            font_ptr,
+            buffer.raw_ptr,
+            raw_features.as_ptr(),
+            raw_features.len() as u32,
+        );
 
         let mut glyph_count: u32 = 0;
         let info_ptr = hb_buffer_get_glyph_infos(buffer.raw_ptr, &mut glyph_count);
         let pos_ptr = hb_buffer_get_glyph_positions(buffer.raw_ptr, &mut glyph_count);
 
-        (glyph_count, info_ptr, pos_ptr)
+        let mut x_scale = 0;
+        let mut y_scale = 0;
+        hb_font_get_scale(font_ptr, &mut x_scale, &mut y_scale);
+
+        (
+            glyph_count,
+            info_ptr,
+            pos_ptr,
+            (x_scale as u32, y_scale as u32),
+        )
     };
 
     let shape = Shaper {
@@ -72,7 +119,8 @@ pub fn shape(buffer: Buffer, font: &Font) -> Vec<Shape> {
         glyph_index: 0,
         glyph_info_ptr: info_ptr,
         glyph_position_ptr: pos_ptr,
-        scale: font.upem,
+        scale,
+        orientation,
     };
 
     shape.collect()
@@ -0,0 +1,14 @@
+use harfbuzz_sys::{
+    hb_unicode_funcs_get_default, hb_unicode_general_category, hb_unicode_general_category_t,
+    hb_unicode_script,
+};
+
+/// Returns ISO 15924 script tag of `c` such as `Latn`, `Zyyy` (common) or `Zinh` (inherited)
+pub fn script(c: char) -> [u8; 4] {
+    unsafe { hb_unicode_script(hb_unicode_funcs_get_default(), c as u32) }.to_be_bytes()
+}
+
+/// Returns Unicode general category of `c` in the Unicode version HarfBuzz knows
+pub fn general_category(c: char) -> hb_unicode_general_category_t {
+    unsafe { hb_unicode_general_category(hb_unicode_funcs_get_default(), c as u32) }
+}
@@ -0,0 +1,146 @@
+use crate::variation::NamedInstance;
+
+/// Name ID of `name` table entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NameId {
+    /// Copyright notice
+    Copyright,
+    /// Family name, shared by at most four styles of regular, italic, bold and bold italic
+    Family,
+    /// Subfamily name such as `Bold Italic`
+    Subfamily,
+    /// Full name such as `Noto Sans Bold`
+    FullName,
+    /// Version string such as `Version 2.001`
+    Version,
+    /// PostScript name such as `NotoSans-Bold`
+    PostScriptName,
+    /// Designer of the typeface
+    Designer,
+    /// Description of the license
+    License,
+    /// URL of the license
+    LicenseUrl,
+    /// Typographic family name, which groups more than four styles
+    TypographicFamily,
+    /// Typographic subfamily name such as `Condensed Light`
+    TypographicSubfamily,
+}
+
+impl NameId {
+    /// Every name ID in order of raw values
+    pub const ALL: [NameId; 11] = [
+        NameId::Copyright,
+        NameId::Family,
+        NameId::Subfamily,
+        NameId::FullName,
+        NameId::Version,
+        NameId::PostScriptName,
+        NameId::Designer,
+        NameId::License,
+        NameId::LicenseUrl,
+        NameId::TypographicFamily,
+        NameId::TypographicSubfamily,
+    ];
+
+    /// Raw name ID of `name` table
+    pub fn raw(self) -> u16 {
+        match self {
+            NameId::Copyright => 0,
+            NameId::Family => 1,
+            NameId::Subfamily => 2,
+            NameId::FullName => 4,
+            NameId::Version => 5,
+            NameId::PostScriptName => 6,
+            NameId::Designer => 9,
+            NameId::License => 13,
+            NameId::LicenseUrl => 14,
+            NameId::TypographicFamily => 16,
+            NameId::TypographicSubfamily => 17,
+        }
+    }
+
+    pub(crate) fn from_raw(raw: u16) -> Option<NameId> {
+        NameId::ALL.into_iter().find(|id| id.raw() == raw)
+    }
+}
+
+/// Entry of `name` table in one language
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameEntry {
+    pub id: NameId,
+    /// BCP-47 language tag such as `en` or `ja`
+    pub language: String,
+    pub value: String,
+}
+
+/// Scripts and languages with OpenType layout in `GSUB` or `GPOS` table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutScript {
+    /// OpenType script tag such as `latn` or `arab`, which is `DFLT` for default script
+    pub tag: [u8; 4],
+    /// OpenType language system tags such as `TRK ` and `ROM `, without the default one
+    pub languages: Vec<[u8; 4]>,
+}
+
+/// What a font is, for font pickers and license checks
+///
+/// Fonts without `OS/2` or `post` table, such as bitmap fonts,
+/// have values derived from style flags of FreeType instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontInfo {
+    /// Entries of `name` table for every `NameId` in every language
+    ///
+    /// Fonts without `name` table have family and subfamily names known to FreeType.
+    pub names: Vec<NameEntry>,
+    /// `usWeightClass` of `OS/2` table, from 100 (thin) to 900 (black)
+    pub weight_class: u16,
+    /// `usWidthClass` of `OS/2` table, from 1 (ultra-condensed) to 9 (ultra-expanded)
+    pub width_class: u16,
+    /// `fsSelection` flags of `OS/2` table
+    ///
+    /// Bit 0 is italic, bit 5 is bold, bit 6 is regular and bit 7 is `USE_TYPO_METRICS`.
+    pub fs_selection: u16,
+    /// PANOSE classification of `OS/2` table, all zeros meaning any
+    pub panose: [u8; 10],
+    /// Italic angle in degrees counter-clockwise from vertical, negative for forward slant
+    pub italic_angle: f32,
+    /// Whether every glyph has the same advance
+    pub fixed_pitch: bool,
+    /// Scripts of `GSUB` and `GPOS` tables, merged and sorted by tag
+    pub scripts: Vec<LayoutScript>,
+}
+
+impl FontInfo {
+    /// Entry of `id`, preferring English and then the first entry
+    pub fn name(&self, id: NameId) -> Option<&str> {
+        let mut entries = self.names.iter().filter(|entry| entry.id == id);
+        let first = entries.clone().next();
+
+        entries
+            .find(|entry| entry.language == "en" || entry.language.starts_with("en-"))
+            .or(first)
+            .map(|entry| entry.value.as_str())
+    }
+
+    pub fn is_italic(&self) -> bool {
+        self.fs_selection & 1 != 0
+    }
+
+    pub fn is_bold(&self) -> bool {
+        self.fs_selection & (1 << 5) != 0
+    }
+}
+
+/// Face in font file, as listed by `Font::faces_in_file`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollectionFace {
+    /// Index of the face, which `Font::from_file` takes
+    pub index: u32,
+    /// Typographic family name, or family name if the font has none
+    pub family: String,
+    /// Typographic subfamily name, or subfamily name if the font has none
+    pub style: String,
+    /// Named instances of variable face, empty otherwise
+    pub named_instances: Vec<NamedInstance>,
+}
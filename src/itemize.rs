@@ -0,0 +1,352 @@
+use std::ops::Range;
+
+use unicode_bidi::{BidiInfo, Level};
+
+use crate::{
+    harfbuzz::{shape::Orientation, unicode},
+    shaping::{Direction, ShapingOptions},
+};
+
+const SCRIPT_COMMON: [u8; 4] = *b"Zyyy";
+const SCRIPT_INHERITED: [u8; 4] = *b"Zinh";
+const SCRIPT_UNKNOWN: [u8; 4] = *b"Zzzz";
+
+/// Scripts which stand upright in vertical text, as in UTR #50
+const UPRIGHT_SCRIPTS: &[&str] = &["Hani", "Hira", "Kana", "Hang", "Bopo", "Yiii"];
+
+/// Paired punctuation, opening character followed by its closing one
+const PAIRED_CHARS: &[(char, char)] = &[
+    ('(', ')'),
+    ('<', '>'),
+    ('[', ']'),
+    ('{', '}'),
+    ('\u{00AB}', '\u{00BB}'),
+    ('\u{2018}', '\u{2019}'),
+    ('\u{201C}', '\u{201D}'),
+    ('\u{2039}', '\u{203A}'),
+    ('\u{3008}', '\u{3009}'),
+    ('\u{300A}', '\u{300B}'),
+    ('\u{300C}', '\u{300D}'),
+    ('\u{300E}', '\u{300F}'),
+    ('\u{3010}', '\u{3011}'),
+    ('\u{3014}', '\u{3015}'),
+    ('\u{3016}', '\u{3017}'),
+    ('\u{3018}', '\u{3019}'),
+    ('\u{301A}', '\u{301B}'),
+    ('\u{FF08}', '\u{FF09}'),
+    ('\u{FF3B}', '\u{FF3D}'),
+    ('\u{FF5B}', '\u{FF5D}'),
+];
+
+/// Splits text into runs in visual order, each of which can be shaped with one buffer
+///
+/// Unless direction is given explicitly, text is split into directional runs
+/// with the Unicode Bidi Algorithm.
+/// Unless script is given explicitly, each of them is split again by script.
+/// Returned options have direction and script of each run filled in.
+pub fn runs(text: &str, options: &ShapingOptions) -> Vec<(Range<usize>, ShapingOptions)> {
+    let directional_runs = match options.direction {
+        Some(direction) => vec![(0..text.len(), direction)],
+        None => bidi_runs(text, options.base_direction),
+    };
+
+    let mut result = Vec::new();
+    for (range, direction) in directional_runs {
+        let mut runs = script_items(text, range, options);
+        // Runs in right-to-left text are laid out from right to left
+        if direction == Direction::RightToLeft {
+            runs.reverse();
+        }
+
+        for (range, script) in runs {
+            let run_options = ShapingOptions {
+                script,
+                direction: Some(direction),
+                ..options.clone()
+            };
+            result.push((range, run_options));
+        }
+    }
+
+    result
+}
+
+/// Splits `range` of text by script in logical order, unless script is given explicitly
+pub fn script_items(
+    text: &str,
+    range: Range<usize>,
+    options: &ShapingOptions,
+) -> Vec<(Range<usize>, Option<String>)> {
+    match &options.script {
+        Some(script) => vec![(range, Some(script.clone()))],
+        None => script_runs(text, range)
+            .into_iter()
+            .map(|(range, script)| {
+                let script = script.map(|tag| String::from_utf8_lossy(&tag).into_owned());
+                (range, script)
+            })
+            .collect(),
+    }
+}
+
+/// How glyphs of `range`, which is one run from `runs`, are placed
+///
+/// In vertical text, runs of scripts other than CJK are rotated sideways.
+pub fn orientation(text: &str, range: Range<usize>, options: &ShapingOptions) -> Orientation {
+    if !options.direction.is_some_and(Direction::is_vertical) {
+        return Orientation::Horizontal;
+    }
+
+    let upright = match &options.script {
+        Some(script) => UPRIGHT_SCRIPTS.contains(&script.as_str()),
+        // Run of common characters only, such as punctuation and digits
+        None => text[range].chars().next().is_some_and(is_upright),
+    };
+    if upright {
+        Orientation::Upright
+    } else {
+        Orientation::Sideways
+    }
+}
+
+/// Whether common character stands upright in vertical text, like fullwidth forms
+fn is_upright(c: char) -> bool {
+    matches!(
+        c,
+        '\u{1100}'..='\u{11FF}'
+            | '\u{2E80}'..='\u{A4CF}'
+            | '\u{AC00}'..='\u{D7FF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FE30}'..='\u{FE4F}'
+            | '\u{FF00}'..='\u{FF60}'
+            | '\u{FFE0}'..='\u{FFE6}'
+            | '\u{20000}'..='\u{3FFFD}'
+    )
+}
+
+/// Paragraph embedding level for base direction, `None` for auto-detection
+pub fn base_level(base_direction: Option<Direction>) -> Option<Level> {
+    match base_direction {
+        Some(Direction::LeftToRight) => Some(Level::ltr()),
+        Some(Direction::RightToLeft) => Some(Level::rtl()),
+        _ => None,
+    }
+}
+
+/// Splits text into directional runs in visual order (UAX #9)
+pub fn bidi_runs(text: &str, base_direction: Option<Direction>) -> Vec<(Range<usize>, Direction)> {
+    let bidi_info = BidiInfo::new(text, base_level(base_direction));
+
+    let mut runs = Vec::new();
+    for paragraph in &bidi_info.paragraphs {
+        let (levels, level_runs) = bidi_info.visual_runs(paragraph, paragraph.range.clone());
+        for run in level_runs {
+            let direction = if levels[run.start].is_rtl() {
+                Direction::RightToLeft
+            } else {
+                Direction::LeftToRight
+            };
+            runs.push((run, direction));
+        }
+    }
+
+    runs
+}
+
+/// Splits `range` of text into runs of the same script in logical order
+///
+/// Common and inherited characters join the surrounding run,
+/// and closing punctuation takes the script of its opening pair.
+/// Script is `None` for runs which have only common characters.
+pub fn script_runs(text: &str, range: Range<usize>) -> Vec<(Range<usize>, Option<[u8; 4]>)> {
+    let mut runs = Vec::new();
+    let mut run_start = range.start;
+    let mut run_script: Option<[u8; 4]> = None;
+    // Opening punctuation not closed yet, with script of the run it was opened in
+    let mut open_pairs: Vec<(usize, Option<[u8; 4]>)> = Vec::new();
+
+    for (offset, c) in text[range.clone()].char_indices() {
+        let mut script = unicode::script(c);
+
+        if let Some(pair) = PAIRED_CHARS.iter().position(|(open, _)| *open == c) {
+            open_pairs.push((pair, run_script));
+        } else if let Some(pair) = PAIRED_CHARS.iter().position(|(_, close)| *close == c) {
+            if let Some(index) = open_pairs.iter().rposition(|(open, _)| *open == pair) {
+                if let Some(open_script) = open_pairs[index].1 {
+                    script = open_script;
+                }
+                open_pairs.truncate(index);
+            }
+        }
+
+        if script == SCRIPT_COMMON || script == SCRIPT_INHERITED || script == SCRIPT_UNKNOWN {
+            continue;
+        }
+
+        match run_script {
+            None => {
+                // Punctuation opened so far belongs to this run
+                for (_, open_script) in open_pairs.iter_mut() {
+                    open_script.get_or_insert(script);
+                }
+                run_script = Some(script);
+            }
+            Some(current) if current != script => {
+                let position = range.start + offset;
+                runs.push((run_start..position, run_script));
+                run_start = position;
+                run_script = Some(script);
+            }
+            _ => {}
+        }
+    }
+
+    if run_start < range.end {
+        runs.push((run_start..range.end, run_script));
+    }
+
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_direction_is_one_run() {
+        assert_eq!(bidi_runs("abc", None), [(0..3, Direction::LeftToRight)]);
+        assert_eq!(
+            bidi_runs("\u{5D0}\u{5D1}", None),
+            [(0..4, Direction::RightToLeft)]
+        );
+    }
+
+    #[test]
+    fn right_to_left_run_is_split_out_of_left_to_right_paragraph() {
+        // Spaces between runs take the paragraph direction
+        assert_eq!(
+            bidi_runs("abc \u{5D0}\u{5D1}\u{5D2} def", None),
+            [
+                (0..4, Direction::LeftToRight),
+                (4..10, Direction::RightToLeft),
+                (10..14, Direction::LeftToRight),
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_of_right_to_left_paragraph_are_in_visual_order() {
+        assert_eq!(
+            bidi_runs("\u{5D0}\u{5D1} abc", None),
+            [
+                (5..8, Direction::LeftToRight),
+                (0..5, Direction::RightToLeft)
+            ]
+        );
+    }
+
+    #[test]
+    fn base_direction_overrides_detection() {
+        assert_eq!(
+            bidi_runs("abc \u{5D0}", Some(Direction::RightToLeft)),
+            [
+                (3..6, Direction::RightToLeft),
+                (0..3, Direction::LeftToRight)
+            ]
+        );
+    }
+
+    #[test]
+    fn explicit_direction_bypasses_bidi() {
+        let options = ShapingOptions {
+            direction: Some(Direction::RightToLeft),
+            script: Some("Latn".to_string()),
+            ..Default::default()
+        };
+        let runs = runs("abc \u{5D0}", &options);
+
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].0, 0..6);
+        assert_eq!(runs[0].1.direction, Some(Direction::RightToLeft));
+    }
+
+    #[test]
+    fn common_characters_join_surrounding_run() {
+        assert_eq!(script_runs("abc", 0..3), [(0..3, Some(*b"Latn"))]);
+        assert_eq!(
+            script_runs("abc \u{430}\u{431}", 0..8),
+            [(0..4, Some(*b"Latn")), (4..8, Some(*b"Cyrl"))]
+        );
+        // Combining acute accent is inherited
+        assert_eq!(script_runs("e\u{301}", 0..3), [(0..3, Some(*b"Latn"))]);
+    }
+
+    #[test]
+    fn common_only_run_has_no_script() {
+        assert_eq!(script_runs("123 !", 0..5), [(0..5, None)]);
+    }
+
+    #[test]
+    fn closing_punctuation_takes_script_of_opening_pair() {
+        // "абв (abc) где"
+        let text = "\u{430}\u{431}\u{432} (abc) \u{433}\u{434}\u{435}";
+        assert_eq!(
+            script_runs(text, 0..text.len()),
+            [
+                (0..8, Some(*b"Cyrl")),
+                (8..11, Some(*b"Latn")),
+                (11..19, Some(*b"Cyrl")),
+            ]
+        );
+        assert_eq!(script_runs("(abc)", 0..5), [(0..5, Some(*b"Latn"))]);
+    }
+
+    #[test]
+    fn script_runs_stay_in_range() {
+        let text = "abc \u{430}\u{431} def";
+        assert_eq!(
+            script_runs(text, 2..8),
+            [(2..4, Some(*b"Latn")), (4..8, Some(*b"Cyrl"))]
+        );
+    }
+
+    #[test]
+    fn script_runs_of_right_to_left_text_are_reversed() {
+        // Hebrew followed by Arabic in one right-to-left run
+        let text = "\u{5D0} \u{627}";
+        let runs = runs(text, &ShapingOptions::default());
+        let scripts: Vec<_> = runs
+            .iter()
+            .map(|(range, options)| (range.clone(), options.script.as_deref()))
+            .collect();
+
+        assert_eq!(scripts, [(3..5, Some("Arab")), (0..3, Some("Hebr"))]);
+    }
+
+    #[test]
+    fn only_cjk_stands_upright_in_vertical_text() {
+        let vertical = |script: Option<&str>| ShapingOptions {
+            direction: Some(Direction::TopToBottom),
+            script: script.map(str::to_string),
+            ..Default::default()
+        };
+        let text = "\u{6F22}abc\u{FF01}";
+
+        assert_eq!(
+            orientation(text, 0..3, &vertical(Some("Hani"))),
+            Orientation::Upright
+        );
+        assert_eq!(
+            orientation(text, 3..6, &vertical(Some("Latn"))),
+            Orientation::Sideways
+        );
+        assert_eq!(
+            orientation(text, 6..9, &vertical(None)),
+            Orientation::Upright
+        );
+        assert_eq!(
+            orientation(text, 3..6, &ShapingOptions::default()),
+            Orientation::Horizontal
+        );
+    }
+}
@@ -1,4 +1,15 @@
 pub mod bitmap;
+pub mod coverage;
+pub mod database;
+pub mod error;
+pub mod fallback;
 pub mod font;
 mod freetype;
 mod harfbuzz;
+pub mod info;
+mod itemize;
+pub mod metrics;
+pub mod paragraph;
+pub mod render;
+pub mod shaping;
+pub mod variation;
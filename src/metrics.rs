@@ -0,0 +1,357 @@
+use crate::bitmap::StringBitmapSize;
+
+/// Metrics of font at the current size, in pixels with y going up from the baseline
+///
+/// Ascender, descender and line gap come from `OS/2` typo metrics if `USE_TYPO_METRICS` is set,
+/// from `hhea` table otherwise, and from `OS/2` win metrics if `hhea` has zeros.
+/// Values missing from the font are taken from glyphs or estimated from the em size.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FontMetrics {
+    /// Height above the baseline which lines reserve
+    pub ascender: f32,
+    /// Depth below the baseline which lines reserve, usually negative
+    pub descender: f32,
+    /// Extra space between the descender of a line and the ascender of the next line
+    pub line_gap: f32,
+    /// Height of lowercase letters such as `x`
+    pub x_height: f32,
+    /// Height of uppercase letters such as `H`
+    pub cap_height: f32,
+    /// Center of the underline stroke, usually negative
+    pub underline_position: f32,
+    pub underline_thickness: f32,
+    /// Center of the strikeout stroke
+    pub strikeout_position: f32,
+    pub strikeout_thickness: f32,
+    /// Largest horizontal advance of any glyph
+    pub max_advance: f32,
+}
+
+impl FontMetrics {
+    /// Distance between baselines of consecutive lines
+    pub fn line_height(&self) -> f32 {
+        self.ascender - self.descender + self.line_gap
+    }
+}
+
+/// Rectangle in pixels, y going up
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    pub x_min: f32,
+    pub y_min: f32,
+    pub x_max: f32,
+    pub y_max: f32,
+}
+
+impl Rect {
+    pub fn width(&self) -> f32 {
+        self.x_max - self.x_min
+    }
+
+    pub fn height(&self) -> f32 {
+        self.y_max - self.y_min
+    }
+
+    /// Smallest rectangle containing both rectangles
+    pub fn union(&self, other: &Rect) -> Rect {
+        Rect {
+            x_min: self.x_min.min(other.x_min),
+            y_min: self.y_min.min(other.y_min),
+            x_max: self.x_max.max(other.x_max),
+            y_max: self.y_max.max(other.y_max),
+        }
+    }
+
+    fn translate(&self, x: f32, y: f32) -> Rect {
+        Rect {
+            x_min: self.x_min + x,
+            y_min: self.y_min + y,
+            x_max: self.x_max + x,
+            y_max: self.y_max + y,
+        }
+    }
+}
+
+/// Glyph placed by shaping
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphPosition {
+    pub glyph_id: u32,
+    /// Byte offset of the first character this glyph comes from
+    pub cluster: u32,
+    /// Horizontal position of the glyph origin from the origin of text, offsets included
+    pub x: f32,
+    /// Vertical position of the glyph origin from the origin of text, y going up
+    pub y: f32,
+    /// Advance to the next glyph along the line, downward in vertical text
+    pub advance: f32,
+}
+
+/// Measured text, in pixels with y going up from the origin of text
+///
+/// The origin is the start of the baseline in horizontal text,
+/// and the top of the center line in vertical text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextMetrics {
+    /// Sum of advances, downward in vertical text
+    pub advance: f32,
+    /// Box reserved for the text whatever glyphs it has
+    ///
+    /// Horizontal text spans from the origin to the advance, and from descender to ascender.
+    /// Vertical text spans from the origin down to the advance, and the line height centered on the center line.
+    pub logical_box: Rect,
+    /// Union of bounding boxes of glyphs, `None` if no glyph has ink such as spaces
+    ///
+    /// Ink may stick out of the logical box, e.g. glyphs with negative left side bearing.
+    pub ink_box: Option<Rect>,
+    /// Distance from the left edge of the rendered bitmap to the origin
+    pub origin_x: f32,
+    /// Distance from the top edge of the rendered bitmap to the origin, which is the baseline of horizontal text
+    pub baseline: f32,
+    /// Glyphs in visual order
+    pub glyphs: Vec<GlyphPosition>,
+    /// Logical box and ink box grown by what rasterization adds around outlines
+    render_box: Rect,
+    vertical: bool,
+}
+
+impl TextMetrics {
+    pub(crate) fn new(
+        advance: f32,
+        logical_box: Rect,
+        ink_box: Option<Rect>,
+        render_box: Rect,
+        glyphs: Vec<GlyphPosition>,
+        vertical: bool,
+    ) -> TextMetrics {
+        let render_box = render_box.union(&logical_box);
+
+        TextMetrics {
+            advance,
+            logical_box,
+            ink_box,
+            // Whole pixels, so that the origin is on the pixel grid
+            origin_x: whole_pixels(-render_box.x_min),
+            baseline: whole_pixels(render_box.y_max),
+            glyphs,
+            render_box,
+            vertical,
+        }
+    }
+
+    /// Metrics of `other` placed right after `self` on the same baseline, or the same center line
+    pub(crate) fn join(self, other: TextMetrics) -> TextMetrics {
+        let (x, y) = if self.vertical {
+            (0.0, -self.advance)
+        } else {
+            (self.advance, 0.0)
+        };
+        let ink_box = match (self.ink_box, other.ink_box) {
+            (Some(ink_box), Some(other)) => Some(ink_box.union(&other.translate(x, y))),
+            (ink_box, other) => ink_box.or(other.map(|other| other.translate(x, y))),
+        };
+        let mut glyphs = self.glyphs;
+        glyphs.extend(other.glyphs.into_iter().map(|glyph| GlyphPosition {
+            x: glyph.x + x,
+            y: glyph.y + y,
+            ..glyph
+        }));
+
+        TextMetrics::new(
+            self.advance + other.advance,
+            self.logical_box.union(&other.logical_box.translate(x, y)),
+            ink_box,
+            self.render_box.union(&other.render_box.translate(x, y)),
+            glyphs,
+            self.vertical || other.vertical,
+        )
+    }
+
+    /// Size of bitmap which text is rendered into, holding every pixel of every glyph
+    pub(crate) fn bitmap_size(&self) -> StringBitmapSize {
+        let left = self.origin_x as u64;
+        let top = self.baseline as u64;
+        let right = whole_pixels(self.render_box.x_max) as u64;
+        let bottom = whole_pixels(-self.render_box.y_min) as u64;
+
+        StringBitmapSize {
+            width: left + right,
+            height: top + bottom,
+            y_max: top,
+            x_min: left,
+        }
+    }
+}
+
+/// Rounds length up to whole pixels, which is zero for negative length
+fn whole_pixels(length: f32) -> f32 {
+    if length > 0.0 {
+        length.ceil()
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x_min: f32, y_min: f32, x_max: f32, y_max: f32) -> Rect {
+        Rect {
+            x_min,
+            y_min,
+            x_max,
+            y_max,
+        }
+    }
+
+    fn glyph(cluster: u32, advance: f32) -> GlyphPosition {
+        GlyphPosition {
+            glyph_id: cluster + 1,
+            cluster,
+            x: 0.0,
+            y: 0.0,
+            advance,
+        }
+    }
+
+    /// Horizontal text of one glyph whose render box is its ink box
+    fn horizontal(cluster: u32, advance: f32, ink_box: Option<Rect>) -> TextMetrics {
+        let logical_box = rect(0.0, -3.0, advance, 8.0);
+        let render_box = ink_box.unwrap_or(logical_box);
+
+        TextMetrics::new(
+            advance,
+            logical_box,
+            ink_box,
+            render_box,
+            vec![glyph(cluster, advance)],
+            false,
+        )
+    }
+
+    #[test]
+    fn joins_horizontal_text_along_baseline() {
+        let first = horizontal(0, 10.0, Some(rect(1.0, 0.0, 9.0, 7.0)));
+        // Negative left side bearing reaches back into the first text
+        let second = horizontal(1, 6.0, Some(rect(-1.0, -2.0, 5.0, 9.5)));
+
+        let joined = first.join(second);
+        assert_eq!(joined.advance, 16.0);
+        assert_eq!(joined.logical_box, rect(0.0, -3.0, 16.0, 8.0));
+        assert_eq!(joined.ink_box, Some(rect(1.0, -2.0, 15.0, 9.5)));
+        assert_eq!(
+            joined
+                .glyphs
+                .iter()
+                .map(|glyph| (glyph.cluster, glyph.x, glyph.y))
+                .collect::<Vec<_>>(),
+            [(0, 0.0, 0.0), (1, 10.0, 0.0)]
+        );
+        // Ink above the ascender moves the baseline down to whole pixels
+        assert_eq!((joined.origin_x, joined.baseline), (0.0, 10.0));
+    }
+
+    #[test]
+    fn joins_ink_of_either_side() {
+        let space = horizontal(0, 4.0, None);
+        let letter = horizontal(1, 6.0, Some(rect(1.0, 0.0, 5.0, 7.0)));
+
+        let joined = space.clone().join(letter.clone());
+        assert_eq!(joined.ink_box, Some(rect(5.0, 0.0, 9.0, 7.0)));
+        assert_eq!(
+            letter.join(space.clone()).ink_box,
+            Some(rect(1.0, 0.0, 5.0, 7.0))
+        );
+        assert_eq!(space.clone().join(space).ink_box, None);
+    }
+
+    #[test]
+    fn joins_vertical_text_downward() {
+        let column = |cluster| {
+            let logical_box = rect(-5.0, -10.0, 5.0, 0.0);
+            TextMetrics::new(
+                10.0,
+                logical_box,
+                Some(rect(-4.0, -9.0, 4.0, -1.0)),
+                logical_box,
+                vec![glyph(cluster, 10.0)],
+                true,
+            )
+        };
+
+        let joined = column(0).join(column(1));
+        assert_eq!(joined.advance, 20.0);
+        assert_eq!(joined.logical_box, rect(-5.0, -20.0, 5.0, 0.0));
+        assert_eq!(joined.ink_box, Some(rect(-4.0, -19.0, 4.0, -1.0)));
+        assert_eq!((joined.glyphs[1].x, joined.glyphs[1].y), (0.0, -10.0));
+        assert_eq!((joined.origin_x, joined.baseline), (5.0, 0.0));
+    }
+
+    #[test]
+    fn joining_empty_text_keeps_metrics() {
+        let text = horizontal(0, 10.0, Some(rect(1.0, 0.0, 9.0, 7.0)));
+        let empty = TextMetrics::new(
+            0.0,
+            rect(0.0, -3.0, 0.0, 8.0),
+            None,
+            rect(0.0, -3.0, 0.0, 8.0),
+            Vec::new(),
+            false,
+        );
+
+        assert_eq!(empty.clone().join(text.clone()), text);
+        assert_eq!(text.clone().join(empty), text);
+    }
+
+    #[test]
+    fn bitmap_holds_render_box_in_whole_pixels() {
+        // Rasterization padding sticks out of the logical box on every side
+        let metrics = TextMetrics::new(
+            10.0,
+            rect(0.0, -3.0, 10.0, 8.0),
+            Some(rect(-0.5, -2.2, 9.4, 7.1)),
+            rect(-1.5, -3.2, 10.4, 8.1),
+            Vec::new(),
+            false,
+        );
+
+        assert_eq!((metrics.origin_x, metrics.baseline), (2.0, 9.0));
+        let size = metrics.bitmap_size();
+        assert_eq!(
+            (size.width, size.height, size.x_min, size.y_max),
+            (13, 13, 2, 9)
+        );
+    }
+
+    #[test]
+    fn render_box_covers_logical_box() {
+        // Glyphs smaller than the line still get a bitmap as tall as the line
+        let metrics = horizontal(0, 4.0, Some(rect(1.0, 1.0, 3.0, 3.0)));
+
+        let size = metrics.bitmap_size();
+        assert_eq!(
+            (size.width, size.height, size.x_min, size.y_max),
+            (4, 11, 0, 8)
+        );
+    }
+
+    #[test]
+    fn rounds_up_to_whole_pixels() {
+        assert_eq!(whole_pixels(1.2), 2.0);
+        assert_eq!(whole_pixels(3.0), 3.0);
+        assert_eq!(whole_pixels(0.0), 0.0);
+        assert_eq!(whole_pixels(-2.5), 0.0);
+    }
+
+    #[test]
+    fn line_height_adds_line_gap() {
+        let metrics = FontMetrics {
+            ascender: 9.5,
+            descender: -2.5,
+            line_gap: 1.0,
+            ..FontMetrics::default()
+        };
+
+        assert_eq!(metrics.line_height(), 13.0);
+    }
+}
@@ -0,0 +1,609 @@
+use std::{collections::BTreeSet, ops::Range, str::FromStr};
+
+use unicode_bidi::BidiInfo;
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+
+use crate::{
+    bitmap::{StringBitmap, StringBitmapSize},
+    error::{Error, Result},
+    font::Font,
+    harfbuzz::shape::Shape,
+    itemize,
+    shaping::{Direction, ShapingOptions},
+};
+
+/// Multi-line text laid out within maximum width
+///
+/// # Notes
+/// - Lines are broken at UAX #14 break opportunities and at hard line breaks like `\n`.
+/// - A word wider than the maximum width is not broken and overflows its line.
+/// - Trailing whitespace of each line is not rendered.
+/// - Lines are aligned and justified within the maximum width, which is the width of rendered bitmap
+///   unless a word overflows it.
+/// - Only horizontal directions are supported.
+pub struct Paragraph {
+    text: String,
+    /// Maximum line width in pixels
+    max_width: u64,
+    options: ShapingOptions,
+    alignment: Alignment,
+    kashida: bool,
+}
+
+/// Horizontal alignment of lines in paragraph
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Alignment {
+    /// Left for left-to-right paragraphs, right for right-to-left paragraphs
+    #[default]
+    Start,
+    /// Right for left-to-right paragraphs, left for right-to-left paragraphs
+    End,
+    Left,
+    Right,
+    Center,
+    /// Spreads extra width over inter-word spaces
+    ///
+    /// The last line and lines ending with hard line break are aligned to start.
+    Justify,
+}
+
+impl Alignment {
+    /// Offset of line from the left edge of line box, when line is narrower by `extra`
+    ///
+    /// Justified lines which cannot be stretched are aligned to start.
+    fn line_offset(self, extra: i64, rtl: bool) -> i64 {
+        match self {
+            Alignment::Left => 0,
+            Alignment::Right => extra,
+            Alignment::Center => extra / 2,
+            Alignment::Start | Alignment::Justify if !rtl => 0,
+            Alignment::Start | Alignment::Justify => extra,
+            Alignment::End if rtl => 0,
+            Alignment::End => extra,
+        }
+    }
+}
+
+impl FromStr for Alignment {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Alignment> {
+        match s {
+            "start" => Ok(Alignment::Start),
+            "end" => Ok(Alignment::End),
+            "left" => Ok(Alignment::Left),
+            "right" => Ok(Alignment::Right),
+            "center" => Ok(Alignment::Center),
+            "justify" => Ok(Alignment::Justify),
+            _ => Err(Error::InvalidAlignment(s.to_string())),
+        }
+    }
+}
+
+/// Glyphs shaped from one run of text, which has one direction and script
+struct ShapedRun {
+    range: Range<usize>,
+    shapes: Vec<Shape>,
+}
+
+impl Paragraph {
+    /// Creates paragraph which breaks lines wider than `max_width` pixels
+    pub fn new(text: &str, max_width: u64) -> Paragraph {
+        Paragraph {
+            text: text.to_string(),
+            max_width,
+            options: ShapingOptions::default(),
+            alignment: Alignment::default(),
+            kashida: false,
+        }
+    }
+
+    /// Sets horizontal alignment of lines
+    pub fn set_alignment(&mut self, alignment: Alignment) {
+        self.alignment = alignment;
+    }
+
+    /// Sets whether justified Arabic lines are stretched with kashida (U+0640 ARABIC TATWEEL)
+    ///
+    /// Width which kashida cannot fill is spread over inter-word spaces.
+    pub fn set_kashida(&mut self, kashida: bool) {
+        self.kashida = kashida;
+    }
+
+    /// Sets shaping options
+    ///
+    /// `base_direction` is the paragraph direction used for reordering each line.
+    /// Vertical `direction` or `base_direction` is an error, since lines are laid out horizontally.
+    pub fn set_options(&mut self, options: ShapingOptions) -> Result<()> {
+        let vertical = [options.direction, options.base_direction]
+            .into_iter()
+            .flatten()
+            .find(|direction| direction.is_vertical());
+        if let Some(direction) = vertical {
+            return Err(Error::VerticalParagraph(direction));
+        }
+        self.options = options;
+
+        Ok(())
+    }
+
+    /// Byte ranges of lines in text, without trailing whitespace and line breaks
+    pub fn lines(&self, font: &mut Font) -> Result<Vec<Range<usize>>> {
+        let bidi_info = BidiInfo::new(&self.text, itemize::base_level(self.options.base_direction));
+        let runs = self.shape(font, &bidi_info)?;
+        let offsets = self.pen_offsets(font, &runs)?;
+
+        Ok(self
+            .break_lines(&offsets)
+            .into_iter()
+            .map(|(line, _)| line)
+            .collect())
+    }
+
+    /// Renders all lines into one bitmap, advancing by line height of the font
+    pub fn render(&self, font: &mut Font) -> Result<StringBitmap> {
+        let bidi_info = BidiInfo::new(&self.text, itemize::base_level(self.options.base_direction));
+        let runs = self.shape(font, &bidi_info)?;
+        let offsets = self.pen_offsets(font, &runs)?;
+        let lines = self.break_lines(&offsets);
+
+        let (ascender, line_height) = font.freetype_font.line_metrics()?;
+        let line_box_width = i64::try_from(self.max_width)
+            .map_err(|_| Error::SizeOverflow)?
+            .checked_mul(64)
+            .ok_or(Error::SizeOverflow)?;
+        let width = lines
+            .iter()
+            .map(|(line, _)| offsets[line.end] - offsets[line.start])
+            .fold(line_box_width, i64::max);
+        let height = line_height * lines.len() as i64;
+        let mut result = StringBitmap::new(
+            StringBitmapSize {
+                width: (width + 63) as u64 >> 6,
+                height: height as u64,
+                y_max: ascender as u64,
+                x_min: 0,
+            },
+            font.freetype_font.style(),
+        )?;
+
+        for (index, (line, hard_break)) in lines.into_iter().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+
+            // Overflowing line starts at the left edge whatever the alignment is
+            let extra = (line_box_width - (offsets[line.end] - offsets[line.start])).max(0);
+            let rtl = self.is_rtl(line.start, &bidi_info);
+            let shapes = self.visual_shapes(line, &runs, &bidi_info)?;
+            let baseline = index as i64 * line_height + ascender;
+            let pen_x = self.alignment.line_offset(extra, rtl);
+
+            if self.alignment == Alignment::Justify && !hard_break {
+                self.draw_justified(font, shapes, extra, rtl, &mut result, baseline)?;
+            } else {
+                font.freetype_font
+                    .draw_string(&shapes, &mut result, pen_x, baseline)?;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Shapes the whole text once, into runs in logical order
+    fn shape(&self, font: &Font, bidi_info: &BidiInfo) -> Result<Vec<ShapedRun>> {
+        let mut runs = Vec::new();
+        for (range, direction) in self.level_runs(bidi_info) {
+            for (range, script) in itemize::script_items(&self.text, range, &self.options) {
+                let run_options = ShapingOptions {
+                    script,
+                    direction: Some(direction),
+                    ..self.options.clone()
+                };
+                let shapes = font.shape_run(&self.text, range.clone(), &run_options)?;
+                runs.push(ShapedRun { range, shapes });
+            }
+        }
+
+        Ok(runs)
+    }
+
+    /// Splits text into runs of the same embedding level in logical order
+    fn level_runs(&self, bidi_info: &BidiInfo) -> Vec<(Range<usize>, Direction)> {
+        if let Some(direction) = self.options.direction {
+            return vec![(0..self.text.len(), direction)];
+        }
+
+        let mut runs: Vec<(Range<usize>, Direction)> = Vec::new();
+        let mut start = 0;
+        for index in 1..=bidi_info.levels.len() {
+            if index == bidi_info.levels.len() || bidi_info.levels[index] != bidi_info.levels[start]
+            {
+                runs.push((start..index, direction_of(bidi_info, start)));
+                start = index;
+            }
+        }
+
+        runs
+    }
+
+    /// Pen position at each byte offset of text in 26.6 fixed-point pixels,
+    /// as if the whole text was one line
+    fn pen_offsets(&self, font: &mut Font, runs: &[ShapedRun]) -> Result<Vec<i64>> {
+        let mut advances = vec![0; self.text.len() + 1];
+        for run in runs {
+            let run_advances = font.freetype_font.advances(&run.shapes)?;
+            for (shape, advance) in run.shapes.iter().zip(run_advances) {
+                let cluster = shape.cluster as usize;
+                *advances
+                    .get_mut(cluster)
+                    .ok_or(Error::OffsetOutOfText(cluster))? += advance;
+            }
+        }
+
+        let mut offsets = vec![0; self.text.len() + 1];
+        for index in 0..self.text.len() {
+            offsets[index + 1] = offsets[index] + advances[index];
+        }
+
+        Ok(offsets)
+    }
+
+    /// Breaks text into lines with greedy algorithm
+    ///
+    /// Each line comes with whether it ends with hard line break or end of text.
+    fn break_lines(&self, offsets: &[i64]) -> Vec<(Range<usize>, bool)> {
+        let line = |start: usize, end: usize| start..start + self.text[start..end].trim_end().len();
+        let width =
+            |range: Range<usize>| (offsets[range.end] - offsets[range.start] + 63) as u64 >> 6;
+
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let mut last_opportunity = None;
+        for (position, opportunity) in linebreaks(&self.text) {
+            if width(line(start, position)) > self.max_width {
+                if let Some(last_opportunity) = last_opportunity.take() {
+                    lines.push((line(start, last_opportunity), false));
+                    start = last_opportunity;
+                }
+            }
+
+            match opportunity {
+                BreakOpportunity::Mandatory => {
+                    lines.push((line(start, position), true));
+                    start = position;
+                    last_opportunity = None;
+                }
+                BreakOpportunity::Allowed => last_opportunity = Some(position),
+            }
+        }
+
+        lines
+    }
+
+    /// Glyphs of line in visual order
+    fn visual_shapes(
+        &self,
+        line: Range<usize>,
+        runs: &[ShapedRun],
+        bidi_info: &BidiInfo,
+    ) -> Result<Vec<Shape>> {
+        let visual_runs = match self.options.direction {
+            Some(direction) => vec![(line, direction)],
+            None => {
+                let paragraph = bidi_info
+                    .paragraphs
+                    .iter()
+                    .find(|paragraph| paragraph.range.contains(&line.start))
+                    .ok_or(Error::OffsetOutOfText(line.start))?;
+                let (levels, runs) = bidi_info.visual_runs(paragraph, line);
+                runs.into_iter()
+                    .map(|run| {
+                        let direction = if levels[run.start].is_rtl() {
+                            Direction::RightToLeft
+                        } else {
+                            Direction::LeftToRight
+                        };
+                        (run, direction)
+                    })
+                    .collect()
+            }
+        };
+
+        let mut shapes = Vec::new();
+        for (visual_run, direction) in visual_runs {
+            let mut pieces: Vec<&ShapedRun> = runs
+                .iter()
+                .filter(|run| run.range.start < visual_run.end && visual_run.start < run.range.end)
+                .collect();
+            // Pieces of right-to-left run are laid out from right to left
+            if direction == Direction::RightToLeft {
+                pieces.reverse();
+            }
+
+            for piece in pieces {
+                shapes.extend(
+                    piece
+                        .shapes
+                        .iter()
+                        .filter(|shape| visual_run.contains(&(shape.cluster as usize)))
+                        .cloned(),
+                );
+            }
+        }
+
+        Ok(shapes)
+    }
+
+    /// Whether paragraph containing byte offset is right-to-left
+    fn is_rtl(&self, index: usize, bidi_info: &BidiInfo) -> bool {
+        match self.options.direction {
+            Some(direction) => direction == Direction::RightToLeft,
+            None => bidi_info
+                .paragraphs
+                .iter()
+                .find(|paragraph| paragraph.range.contains(&index))
+                .is_some_and(|paragraph| paragraph.level.is_rtl()),
+        }
+    }
+
+    /// Draws line stretched by `extra` 26.6 fixed-point pixels,
+    /// with kashida and wider inter-word spaces
+    fn draw_justified(
+        &self,
+        font: &mut Font,
+        mut shapes: Vec<Shape>,
+        mut extra: i64,
+        rtl: bool,
+        result: &mut StringBitmap,
+        baseline: i64,
+    ) -> Result<()> {
+        if self.kashida {
+            extra = self.insert_kashida(font, &mut shapes, extra)?;
+        }
+
+        let mut spaces = Vec::new();
+        // Space at the end of line is not stretched
+        for (index, shape) in shapes
+            .iter()
+            .enumerate()
+            .take(shapes.len().saturating_sub(1))
+        {
+            if is_word_separator(self.char_at(shape.cluster as usize)?) {
+                spaces.push(index);
+            }
+        }
+        if spaces.is_empty() {
+            // Nothing to stretch
+            let pen_x = Alignment::Justify.line_offset(extra, rtl);
+            font.freetype_font
+                .draw_string(&shapes, result, pen_x, baseline)?;
+            return Ok(());
+        }
+
+        let count = spaces.len() as i64;
+        let mut pen_x = 0;
+        let mut start = 0;
+        for (nth, index) in spaces.into_iter().enumerate() {
+            pen_x =
+                font.freetype_font
+                    .draw_string(&shapes[start..=index], result, pen_x, baseline)?;
+            pen_x += share(extra, count, nth);
+            start = index + 1;
+        }
+        font.freetype_font
+            .draw_string(&shapes[start..], result, pen_x, baseline)?;
+
+        Ok(())
+    }
+
+    /// Inserts tatweel glyphs between joined Arabic letters, and returns width left to fill
+    fn insert_kashida(&self, font: &mut Font, shapes: &mut Vec<Shape>, extra: i64) -> Result<i64> {
+        let options = ShapingOptions {
+            script: Some("Arab".to_string()),
+            direction: Some(Direction::RightToLeft),
+            ..ShapingOptions::default()
+        };
+        let tatweel = font.shape_run("\u{0640}", 0..2, &options)?;
+        if tatweel.len() != 1 || tatweel[0].glyph_id == 0 {
+            return Ok(extra);
+        }
+        let tatweel_advance = font.freetype_font.advances(&tatweel)?[0];
+        if tatweel_advance <= 0 {
+            return Ok(extra);
+        }
+
+        let clusters: BTreeSet<usize> = shapes.iter().map(|shape| shape.cluster as usize).collect();
+        // Tatweel goes to the left of first glyph of a cluster in visual order
+        let mut opportunities = Vec::new();
+        for index in 0..shapes.len() {
+            let first = index == 0 || shapes[index - 1].cluster != shapes[index].cluster;
+            if first && self.joins_next(shapes[index].cluster as usize, &clusters)? {
+                opportunities.push(index);
+            }
+        }
+        let kashida_count = extra / tatweel_advance;
+        if opportunities.is_empty() || kashida_count == 0 {
+            return Ok(extra);
+        }
+
+        let count = opportunities.len() as i64;
+        // Insert from the end, so that earlier indices stay valid
+        for (nth, index) in opportunities.into_iter().enumerate().rev() {
+            let repeat = share(kashida_count, count, nth);
+            for _ in 0..repeat {
+                let shape = Shape {
+                    cluster: shapes[index].cluster,
+                    ..tatweel[0].clone()
+                };
+                shapes.insert(index, shape);
+            }
+        }
+
+        Ok(extra - kashida_count * tatweel_advance)
+    }
+
+    /// Whether Arabic letter at cluster joins to the letter of next cluster
+    fn joins_next(&self, cluster: usize, clusters: &BTreeSet<usize>) -> Result<bool> {
+        if !is_dual_joining(self.char_at(cluster)?) {
+            return Ok(false);
+        }
+
+        match clusters.range(cluster + 1..).next() {
+            Some(&next) => Ok(is_arabic_letter(self.char_at(next)?)),
+            None => Ok(false),
+        }
+    }
+
+    /// Character at byte offset, which must be at a character of text
+    fn char_at(&self, index: usize) -> Result<char> {
+        self.text
+            .get(index..)
+            .and_then(|rest| rest.chars().next())
+            .ok_or(Error::OffsetOutOfText(index))
+    }
+}
+
+/// Part of `total` which `nth` of `count` places takes when `total` is spread evenly
+///
+/// Remainder goes to the first places, one each.
+fn share(total: i64, count: i64, nth: usize) -> i64 {
+    total / count + i64::from((nth as i64) < total % count)
+}
+
+/// Characters whose width is adjusted by justification, as in CSS Text Module Level 3
+fn is_word_separator(c: char) -> bool {
+    matches!(
+        c,
+        ' ' | '\u{00A0}' | '\u{1361}' | '\u{10100}' | '\u{10101}' | '\u{1039F}' | '\u{1091F}'
+    )
+}
+
+/// Arabic letters which join to the previous letter
+fn is_arabic_letter(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0620}' | '\u{0622}'..='\u{063F}' | '\u{0641}'..='\u{064A}' | '\u{066E}'..='\u{0673}'
+            | '\u{0675}'..='\u{06D3}' | '\u{06D5}' | '\u{06FA}'..='\u{06FC}' | '\u{06FF}'
+    )
+}
+
+/// Arabic letters which join to both previous and next letter
+fn is_dual_joining(c: char) -> bool {
+    let right_joining = matches!(
+        c,
+        '\u{0622}'..='\u{0625}' | '\u{0627}' | '\u{0629}' | '\u{062F}'..='\u{0632}' | '\u{0648}'
+            | '\u{0671}'..='\u{0673}' | '\u{0675}'..='\u{0677}' | '\u{0688}'..='\u{0699}'
+            | '\u{06C0}' | '\u{06C3}'..='\u{06CB}' | '\u{06CD}' | '\u{06CF}' | '\u{06D2}'..='\u{06D3}'
+            | '\u{06D5}'
+    );
+    is_arabic_letter(c) && !right_joining
+}
+
+fn direction_of(bidi_info: &BidiInfo, index: usize) -> Direction {
+    if bidi_info.levels[index].is_rtl() {
+        Direction::RightToLeft
+    } else {
+        Direction::LeftToRight
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pen offsets of text whose every byte advances 10 pixels
+    fn offsets(text: &str) -> Vec<i64> {
+        (0..=text.len() as i64)
+            .map(|index| index * 10 * 64)
+            .collect()
+    }
+
+    fn break_lines(text: &str, max_width: u64) -> Vec<(Range<usize>, bool)> {
+        Paragraph::new(text, max_width).break_lines(&offsets(text))
+    }
+
+    #[test]
+    fn breaks_at_last_opportunity_within_max_width() {
+        assert_eq!(break_lines("ab cd ef", 50), [(0..5, false), (6..8, true)]);
+    }
+
+    #[test]
+    fn breaks_at_mandatory_breaks() {
+        assert_eq!(
+            break_lines("ab\ncd\r\n\nef", 1000),
+            [(0..2, true), (3..5, true), (7..7, true), (8..10, true)]
+        );
+    }
+
+    #[test]
+    fn zero_max_width_puts_each_word_on_its_line() {
+        assert_eq!(
+            break_lines("ab cd ef", 0),
+            [(0..2, false), (3..5, false), (6..8, true)]
+        );
+    }
+
+    #[test]
+    fn word_wider_than_max_width_overflows_its_line() {
+        assert_eq!(
+            break_lines("abcdef gh ij", 30),
+            [(0..6, false), (7..9, false), (10..12, true)]
+        );
+        assert_eq!(break_lines("abcdef", 30), [(0..6, true)]);
+    }
+
+    #[test]
+    fn trailing_whitespace_is_not_part_of_line() {
+        assert_eq!(
+            break_lines("ab   \ncd  ", 1000),
+            [(0..2, true), (6..8, true)]
+        );
+    }
+
+    #[test]
+    fn lines_are_aligned_within_line_box() {
+        assert_eq!(Alignment::Left.line_offset(100, true), 0);
+        assert_eq!(Alignment::Right.line_offset(100, false), 100);
+        assert_eq!(Alignment::Center.line_offset(101, false), 50);
+        assert_eq!(Alignment::Start.line_offset(100, false), 0);
+        assert_eq!(Alignment::Start.line_offset(100, true), 100);
+        assert_eq!(Alignment::End.line_offset(100, false), 100);
+        assert_eq!(Alignment::End.line_offset(100, true), 0);
+        assert_eq!(Alignment::Justify.line_offset(100, true), 100);
+    }
+
+    #[test]
+    fn extra_width_is_spread_evenly_with_remainder_first() {
+        let shares: Vec<i64> = (0..3).map(|nth| share(11, 3, nth)).collect();
+
+        assert_eq!(shares, [4, 4, 3]);
+        assert_eq!(shares.iter().sum::<i64>(), 11);
+        assert_eq!(share(0, 3, 0), 0);
+    }
+
+    #[test]
+    fn parses_alignments() {
+        assert_eq!("justify".parse::<Alignment>().unwrap(), Alignment::Justify);
+        assert_eq!("end".parse::<Alignment>().unwrap(), Alignment::End);
+        assert!(matches!(
+            "middle".parse::<Alignment>(),
+            Err(Error::InvalidAlignment(_))
+        ));
+    }
+
+    #[test]
+    fn offset_out_of_text_is_error() {
+        let paragraph = Paragraph::new("a\u{E9}", 0);
+
+        assert_eq!(paragraph.char_at(1).unwrap(), '\u{E9}');
+        assert!(matches!(
+            paragraph.char_at(2),
+            Err(Error::OffsetOutOfText(2))
+        ));
+        assert!(matches!(
+            paragraph.char_at(3),
+            Err(Error::OffsetOutOfText(3))
+        ));
+    }
+}
@@ -0,0 +1,353 @@
+use std::{str::FromStr, sync::OnceLock};
+
+use crate::error::Error;
+
+/// How glyphs are rasterized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    /// 8-bit grayscale coverage
+    Normal,
+    /// 1-bit coverage without antialiasing, e.g. for e-ink displays
+    Mono,
+    /// Subpixel coverage for displays with horizontal RGB stripes (default)
+    #[default]
+    Lcd,
+    /// Subpixel coverage for displays with vertical RGB stripes
+    LcdV,
+    /// 8-bit signed distance field, where 128 is on the outline
+    Sdf,
+}
+
+impl FromStr for RenderMode {
+    type Err = Error;
+
+    /// Parses `normal`, `mono`, `lcd`, `lcd-v` or `sdf`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "normal" => Ok(RenderMode::Normal),
+            "mono" => Ok(RenderMode::Mono),
+            "lcd" => Ok(RenderMode::Lcd),
+            "lcd-v" => Ok(RenderMode::LcdV),
+            "sdf" => Ok(RenderMode::Sdf),
+            _ => Err(Error::InvalidRenderMode(s.to_string())),
+        }
+    }
+}
+
+/// How glyph outlines are fitted to the pixel grid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Hinting {
+    /// Outlines are rendered as designed
+    None,
+    /// Only vertical positions are fitted, which keeps glyph shapes and advances
+    Light,
+    /// Native hinting of the font, or the auto-hinter if it has no hinting instructions
+    #[default]
+    Normal,
+    /// Strong hinting for monochrome rendering
+    Mono,
+    /// Always the auto-hinter of FreeType, ignoring hinting instructions of the font
+    Auto,
+}
+
+impl FromStr for Hinting {
+    type Err = Error;
+
+    /// Parses `none`, `light`, `normal`, `mono` or `auto`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Hinting::None),
+            "light" => Ok(Hinting::Light),
+            "normal" => Ok(Hinting::Normal),
+            "mono" => Ok(Hinting::Mono),
+            "auto" => Ok(Hinting::Auto),
+            _ => Err(Error::InvalidHinting(s.to_string())),
+        }
+    }
+}
+
+/// RGBA color with straight (not premultiplied) alpha
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgba(0, 0, 0, 255);
+    pub const WHITE: Color = Color::rgba(255, 255, 255, 255);
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color { r, g, b, a }
+    }
+}
+
+impl FromStr for Color {
+    type Err = Error;
+
+    /// Parses `#rrggbb`, `#rrggbbaa` or `transparent`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("transparent") {
+            return Ok(Color::TRANSPARENT);
+        }
+
+        let invalid = || Error::InvalidColor(s.to_string());
+        let hex = s.strip_prefix('#').ok_or_else(invalid)?;
+        // from_str_radix alone would take signs like `+f`
+        if !(hex.len() == 6 || hex.len() == 8) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let channel = |index: usize| {
+            hex.get(index * 2..index * 2 + 2)
+                .map(|digits| u8::from_str_radix(digits, 16).map_err(|_| invalid()))
+                .unwrap_or(Ok(255))
+        };
+
+        Ok(Color::rgba(
+            channel(0)?,
+            channel(1)?,
+            channel(2)?,
+            channel(3)?,
+        ))
+    }
+}
+
+/// How color channels of `StringBitmap` relate to its alpha channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlphaMode {
+    /// Color channels are independent of alpha, as in PNG
+    #[default]
+    Straight,
+    /// Color channels are multiplied by alpha, as most compositors expect
+    Premultiplied,
+}
+
+/// How glyph coverage is blended with background
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Blending {
+    /// Blends sRGB-encoded values as they are, like most UI toolkits
+    ///
+    /// Dark text on light background looks bolder than light text on dark background.
+    #[default]
+    Srgb,
+    /// Blends in linear light, which is physically correct
+    /// but makes dark text on light background look thin
+    Linear,
+    /// Blends sRGB-encoded values with coverage corrected by lookup table,
+    /// like Skia and DirectWrite
+    ///
+    /// Coverage is corrected as if blended with `gamma` against contrasting background,
+    /// then `contrast` darkens thin stems like stem darkening of FreeType.
+    /// Skia uses gamma 1.2 and contrast 0.2, DirectWrite uses gamma 1.8 and contrast 0.5.
+    Gamma { gamma: f32, contrast: f32 },
+}
+
+impl FromStr for Blending {
+    type Err = Error;
+
+    /// Parses `srgb`, `linear` or `gamma:<gamma>,<contrast>` like `gamma:1.8,0.5`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "srgb" => Ok(Blending::Srgb),
+            "linear" => Ok(Blending::Linear),
+            lower => lower
+                .strip_prefix("gamma:")
+                .and_then(|parameters| parameters.split_once(','))
+                .and_then(|(gamma, contrast)| {
+                    Some(Blending::Gamma {
+                        gamma: gamma.trim().parse().ok()?,
+                        contrast: contrast.trim().parse().ok()?,
+                    })
+                })
+                .ok_or_else(|| Error::InvalidBlending(s.to_string())),
+        }
+    }
+}
+
+impl Blending {
+    /// Lookup table correcting 8-bit coverage of glyphs drawn with `foreground`
+    pub(crate) fn coverage_table(self, foreground: Color) -> [u8; 256] {
+        let mut table = [0; 256];
+        for (coverage, corrected) in table.iter_mut().enumerate() {
+            *corrected = coverage as u8;
+        }
+
+        let Blending::Gamma { gamma, contrast } = self else {
+            return table;
+        };
+        let src = (0.2126 * foreground.r as f32
+            + 0.7152 * foreground.g as f32
+            + 0.0722 * foreground.b as f32)
+            / 255.0;
+        let dst = 1.0 - src;
+        for (coverage, corrected) in table.iter_mut().enumerate() {
+            let alpha = coverage as f32 / 255.0;
+            let alpha = alpha + contrast * alpha * (1.0 - alpha);
+            let out = (src.powf(gamma) * alpha + dst.powf(gamma) * (1.0 - alpha)).powf(1.0 / gamma);
+            // Coverage giving the same result when blended without gamma
+            let alpha = if (src - dst).abs() < 1e-3 {
+                alpha
+            } else {
+                (out - dst) / (src - dst)
+            };
+            *corrected = (alpha.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+
+        table
+    }
+}
+
+/// Converts sRGB-encoded channel into linear light
+pub(crate) fn srgb_to_linear(channel: u8) -> f32 {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+
+    TABLE.get_or_init(|| {
+        let mut table = [0.0; 256];
+        for (channel, linear) in table.iter_mut().enumerate() {
+            let channel = channel as f32 / 255.0;
+            *linear = if channel <= 0.04045 {
+                channel / 12.92
+            } else {
+                ((channel + 0.055) / 1.055).powf(2.4)
+            };
+        }
+        table
+    })[channel as usize]
+}
+
+/// Converts linear light into sRGB-encoded channel
+pub(crate) fn linear_to_srgb(linear: f32) -> u8 {
+    const SIZE: usize = 4096;
+    static TABLE: OnceLock<Vec<u8>> = OnceLock::new();
+
+    let table = TABLE.get_or_init(|| {
+        (0..SIZE)
+            .map(|index| {
+                let linear = index as f32 / (SIZE - 1) as f32;
+                let channel = if linear <= 0.0031308 {
+                    linear * 12.92
+                } else {
+                    1.055 * linear.powf(1.0 / 2.4) - 0.055
+                };
+                (channel * 255.0).round() as u8
+            })
+            .collect()
+    });
+
+    table[(linear.clamp(0.0, 1.0) * (SIZE - 1) as f32).round() as usize]
+}
+
+/// Colors of rendered text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    /// Color of glyphs, color glyphs like emoji keep their own colors
+    pub foreground: Color,
+    /// Color which bitmap is filled with before drawing glyphs
+    pub background: Color,
+    /// Alpha representation of rendered bitmap
+    pub alpha_mode: AlphaMode,
+    /// How glyph coverage is blended with background
+    pub blending: Blending,
+}
+
+impl Default for TextStyle {
+    /// White text on transparent background, with straight alpha and sRGB blending
+    fn default() -> Self {
+        TextStyle {
+            foreground: Color::WHITE,
+            background: Color::TRANSPARENT,
+            alpha_mode: AlphaMode::Straight,
+            blending: Blending::Srgb,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(
+            "#ff8000".parse::<Color>().unwrap(),
+            Color::rgba(255, 128, 0, 255)
+        );
+        assert_eq!(
+            "#FF800040".parse::<Color>().unwrap(),
+            Color::rgba(255, 128, 0, 64)
+        );
+        assert_eq!("Transparent".parse::<Color>().unwrap(), Color::TRANSPARENT);
+    }
+
+    #[test]
+    fn rejects_signs_in_hex_colors() {
+        assert!(matches!(
+            "#+f+f+f".parse::<Color>(),
+            Err(Error::InvalidColor(_))
+        ));
+        assert!("#-f0000".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn rejects_hex_colors_of_wrong_length() {
+        for color in ["#", "#fff", "#fffff", "#fffffff", "#fffffffff", "ffffff"] {
+            assert!(color.parse::<Color>().is_err(), "{:?}", color);
+        }
+    }
+
+    #[test]
+    fn rejects_non_ascii_colors() {
+        // Six bytes, but not six characters
+        assert!("#\u{E9}\u{E9}\u{E9}".parse::<Color>().is_err());
+        assert!("#ff\u{FF10}0".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn parses_blendings() {
+        assert_eq!("sRGB".parse::<Blending>().unwrap(), Blending::Srgb);
+        assert_eq!("linear".parse::<Blending>().unwrap(), Blending::Linear);
+        assert_eq!(
+            "gamma:1.8, 0.5".parse::<Blending>().unwrap(),
+            Blending::Gamma {
+                gamma: 1.8,
+                contrast: 0.5
+            }
+        );
+        for blending in ["gamma", "gamma:1.8", "gamma:x,0.5", "gray"] {
+            assert!(
+                matches!(blending.parse::<Blending>(), Err(Error::InvalidBlending(_))),
+                "{:?}",
+                blending
+            );
+        }
+    }
+
+    #[test]
+    fn coverage_table_is_identity_without_gamma() {
+        let table = Blending::Srgb.coverage_table(Color::BLACK);
+
+        assert!(table.iter().enumerate().all(|(i, &c)| c as usize == i));
+    }
+
+    #[test]
+    fn gamma_coverage_table_keeps_ends() {
+        let table = Blending::Gamma {
+            gamma: 1.8,
+            contrast: 0.5,
+        }
+        .coverage_table(Color::BLACK);
+
+        assert_eq!((table[0], table[255]), (0, 255));
+        assert!(table.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn srgb_round_trips_through_linear() {
+        for channel in 0..=255 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(channel)), channel);
+        }
+    }
+}
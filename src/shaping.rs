@@ -0,0 +1,178 @@
+use std::str::FromStr;
+
+use crate::{error::Error, harfbuzz::feature};
+
+/// Text direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Left to right
+    LeftToRight,
+    /// Right to left
+    RightToLeft,
+    /// Top to bottom
+    TopToBottom,
+    /// Bottom to top, which can be parsed but not laid out
+    BottomToTop,
+}
+
+impl Direction {
+    /// Whether text flows from top to bottom or bottom to top
+    pub fn is_vertical(self) -> bool {
+        matches!(self, Direction::TopToBottom | Direction::BottomToTop)
+    }
+}
+
+impl FromStr for Direction {
+    type Err = Error;
+
+    /// Parses `ltr`, `rtl`, `ttb` or `btt`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ltr" => Ok(Direction::LeftToRight),
+            "rtl" => Ok(Direction::RightToLeft),
+            "ttb" => Ok(Direction::TopToBottom),
+            "btt" => Ok(Direction::BottomToTop),
+            _ => Err(Error::InvalidDirection(s.to_string())),
+        }
+    }
+}
+
+/// How HarfBuzz merges characters into clusters
+///
+/// See `hb_buffer_cluster_level_t` for details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClusterLevel {
+    /// Clusters are grapheme-based and monotone (default)
+    #[default]
+    MonotoneGraphemes,
+    /// Clusters are character-based and monotone
+    MonotoneCharacters,
+    /// Clusters are character-based and may be out of order
+    Characters,
+}
+
+/// Segment properties of text to be shaped
+///
+/// Properties left as `None` are guessed from the text by HarfBuzz,
+/// which can go wrong for short strings, Han text and mixed-direction text.
+#[derive(Debug, Clone, Default)]
+pub struct ShapingOptions {
+    /// ISO 15924 script tag such as `Latn`, `Arab` or `Hani`
+    pub script: Option<String>,
+    /// BCP-47 language tag such as `ja`, `zh-Hans` or `zh-Hant`
+    pub language: Option<String>,
+    /// Text direction
+    ///
+    /// Setting this shapes the whole text as one run in the given direction,
+    /// bypassing bidirectional itemization.
+    /// In vertical text, runs of scripts other than CJK are rotated sideways.
+    pub direction: Option<Direction>,
+    /// Paragraph base direction used by the Unicode Bidi Algorithm
+    ///
+    /// Only `LeftToRight` and `RightToLeft` are meaningful here.
+    /// If `None`, it's detected from the first strong character of each paragraph.
+    pub base_direction: Option<Direction>,
+    /// Cluster level
+    pub cluster_level: ClusterLevel,
+    /// OpenType features applied on top of the font's default features
+    pub features: Vec<Feature>,
+}
+
+/// OpenType feature setting such as `liga`, `kern`, `smcp`, `ss01` or `tnum`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Feature {
+    /// Four-letter feature tag
+    pub tag: [u8; 4],
+    /// `0` disables the feature, `1` enables it,
+    /// larger values select alternates for features like `salt` and `aalt`
+    pub value: u32,
+    /// First cluster the feature applies to (inclusive)
+    pub start: u32,
+    /// Last cluster the feature applies to (exclusive)
+    ///
+    /// `u32::MAX` means end of text.
+    pub end: u32,
+}
+
+impl Feature {
+    /// Creates feature setting applied to the whole text
+    pub fn new(tag: [u8; 4], value: u32) -> Feature {
+        Feature {
+            tag,
+            value,
+            start: 0,
+            end: u32::MAX,
+        }
+    }
+
+    /// Limits feature setting to clusters in `start..end`
+    pub fn with_range(self, start: u32, end: u32) -> Feature {
+        Feature { start, end, ..self }
+    }
+}
+
+impl FromStr for Feature {
+    type Err = Error;
+
+    /// Parses CSS or HarfBuzz style feature string
+    /// such as `-liga`, `+ss01`, `tnum=1` and `aalt[3:5]=2`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        feature::parse(s).ok_or_else(|| Error::InvalidFeature(s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_css_and_harfbuzz_feature_strings() {
+        assert_eq!(
+            "-liga".parse::<Feature>().unwrap(),
+            Feature::new(*b"liga", 0)
+        );
+        assert_eq!(
+            "+ss01".parse::<Feature>().unwrap(),
+            Feature::new(*b"ss01", 1)
+        );
+        assert_eq!(
+            "tnum=1".parse::<Feature>().unwrap(),
+            Feature::new(*b"tnum", 1)
+        );
+        assert_eq!(
+            "\"smcp\" on".parse::<Feature>().unwrap(),
+            Feature::new(*b"smcp", 1)
+        );
+    }
+
+    #[test]
+    fn parses_feature_ranges() {
+        assert_eq!(
+            "aalt[3:5]=2".parse::<Feature>().unwrap(),
+            Feature::new(*b"aalt", 2).with_range(3, 5)
+        );
+        assert_eq!(
+            "kern[3:]".parse::<Feature>().unwrap(),
+            Feature::new(*b"kern", 1).with_range(3, u32::MAX)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_feature_strings() {
+        for feature in ["", "=1", "liga=", "liga[", "liga[1:2"] {
+            assert!(
+                matches!(feature.parse::<Feature>(), Err(Error::InvalidFeature(_))),
+                "{:?}",
+                feature
+            );
+        }
+    }
+
+    #[test]
+    fn parses_directions() {
+        assert_eq!("RTL".parse::<Direction>().unwrap(), Direction::RightToLeft);
+        assert!("btt".parse::<Direction>().unwrap().is_vertical());
+        assert!(!"ltr".parse::<Direction>().unwrap().is_vertical());
+        assert!("up".parse::<Direction>().is_err());
+    }
+}
@@ -0,0 +1,57 @@
+use crate::error::{Error, Result};
+
+/// Design axis of variable font, such as `wght`, `wdth`, `opsz` and `slnt`
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariationAxis {
+    /// Four-letter axis tag
+    pub tag: [u8; 4],
+    /// Display name from `name` table, empty if the font has none
+    pub name: String,
+    /// Minimum design coordinate
+    pub min: f32,
+    /// Design coordinate of the default instance
+    pub default: f32,
+    /// Maximum design coordinate
+    pub max: f32,
+    /// Whether the axis should not be exposed directly in user interfaces
+    pub hidden: bool,
+}
+
+/// Named instance of variable font, such as `Bold` or `Condensed Light`
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedInstance {
+    /// Subfamily name from `name` table, empty if the font has none
+    pub name: String,
+    /// Design coordinates, one for each axis in order of `Font::variation_axes`
+    pub coordinates: Vec<f32>,
+}
+
+/// Parses axis tag, padding tags shorter than four letters with spaces
+pub(crate) fn parse_tag(tag: &str) -> Result<[u8; 4]> {
+    if tag.is_empty() || tag.len() > 4 || !tag.bytes().all(|byte| byte.is_ascii_graphic()) {
+        return Err(Error::InvalidVariation(tag.to_string()));
+    }
+
+    let mut raw = [b' '; 4];
+    raw[..tag.len()].copy_from_slice(tag.as_bytes());
+
+    Ok(raw)
+}
+
+/// Design coordinates of every axis, defaults unless given in `variations`
+///
+/// Values are clamped to the range of the axis, and tags the font has no axis of are ignored.
+pub(crate) fn design_coordinates(
+    axes: &[VariationAxis],
+    variations: &[([u8; 4], f32)],
+) -> Vec<f32> {
+    axes.iter()
+        .map(|axis| {
+            variations
+                .iter()
+                .rev()
+                .find(|(tag, _)| *tag == axis.tag)
+                .map_or(axis.default, |(_, value)| value.clamp(axis.min, axis.max))
+        })
+        .collect()
+}